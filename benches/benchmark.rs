@@ -1,7 +1,10 @@
-use std::path::Path;
+use std::{fs::File, io::BufReader, path::Path};
 
-use criterion::{criterion_group, criterion_main, Criterion};
-use libreeb::RawFileReader;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use libreeb::{
+    parse_header, slice_events, slice_events_reuse, Event, EventDecoder, Evt3Decoder, EvtReader,
+    RawFileReader, SliceBy,
+};
 
 pub fn evt3_decode_benchmark(c: &mut Criterion) {
     c.bench_function("evt3_decode", |b| {
@@ -15,6 +18,19 @@ pub fn evt3_decode_benchmark(c: &mut Criterion) {
     });
 }
 
+pub fn evt3_for_each_benchmark(c: &mut Criterion) {
+    c.bench_function("evt3_for_each", |b| {
+        b.iter(|| {
+            let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+            let mut reader =
+                RawFileReader::new(Path::new(&path)).expect("Failed to open test file");
+            let mut count = 0;
+            reader.for_each_event(|_| count += 1);
+            count
+        })
+    });
+}
+
 pub fn evt21_decode_benchmark(c: &mut Criterion) {
     c.bench_function("evt21_decode", |b| {
         b.iter(|| {
@@ -27,5 +43,59 @@ pub fn evt21_decode_benchmark(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, evt3_decode_benchmark, evt21_decode_benchmark);
+pub fn evt3_buffer_size_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("evt3_buffer_size");
+    for buffer_size in [512, 64 * 1024] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(buffer_size),
+            &buffer_size,
+            |b, &buffer_size| {
+                b.iter(|| {
+                    let file = File::open("data/openeb/gen4_evt3_hand.raw")
+                        .expect("Failed to open test file");
+                    let mut reader = BufReader::with_capacity(64 * 1024, file);
+                    parse_header(&mut reader).expect("Failed to parse header");
+                    EvtReader::with_capacity(reader, Evt3Decoder::new(), buffer_size).count()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+pub fn evt3_slice_benchmark(c: &mut Criterion) {
+    let events: Vec<Event> = RawFileReader::new(Path::new("data/openeb/gen4_evt3_hand.raw"))
+        .expect("Failed to open test file")
+        .read_events()
+        .collect();
+
+    let mut group = c.benchmark_group("evt3_slice_count");
+    group.bench_function("alloc_per_slice", |b| {
+        b.iter(|| {
+            slice_events(events.iter().copied(), SliceBy::Count(1000))
+                .map(|slice| slice.len())
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("reuse", |b| {
+        b.iter(|| {
+            let mut slices = slice_events_reuse(events.iter().copied(), SliceBy::Count(1000));
+            let mut total = 0;
+            while let Some(slice) = slices.next_slice() {
+                total += slice.len();
+            }
+            total
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    evt3_decode_benchmark,
+    evt3_for_each_benchmark,
+    evt21_decode_benchmark,
+    evt3_buffer_size_benchmark,
+    evt3_slice_benchmark
+);
 criterion_main!(benches);
@@ -0,0 +1,25 @@
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use libreeb::RawFileReader;
+
+pub fn evt3_mmap_decode_benchmark(c: &mut Criterion) {
+    let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+    let mut group = c.benchmark_group("evt3_whole_file_source");
+    group.bench_function("buffered", |b| {
+        b.iter(|| {
+            let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+            reader.read_events().count()
+        })
+    });
+    group.bench_function("mmap", |b| {
+        b.iter(|| {
+            let mut reader = RawFileReader::new_mmap(path).expect("Failed to map test file");
+            reader.read_events().count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, evt3_mmap_decode_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use libreeb::{decode_to_soa, RawFileReader};
+
+pub fn evt3_par_decode_benchmark(c: &mut Criterion) {
+    let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+    let mut group = c.benchmark_group("evt3_whole_file");
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            let reader = RawFileReader::new(path).expect("Failed to open test file");
+            decode_to_soa(reader).len()
+        })
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| {
+            let reader = RawFileReader::new(path).expect("Failed to open test file");
+            reader
+                .par_decode(None)
+                .expect("Failed to decode test file")
+                .len()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, evt3_par_decode_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,799 @@
+use crate::Event;
+use itertools::Itertools;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Keeps only the CD events with polarity `p`, triggers are dropped
+pub fn filter_polarity(events: impl Iterator<Item = Event>, p: u8) -> impl Iterator<Item = Event> {
+    events.filter(move |event| matches!(event, Event::CD { p: polarity, .. } if *polarity == p))
+}
+
+/// Splits the CD events into `(positive, negative)` streams, triggers are dropped
+pub fn split_polarity(
+    events: &[Event],
+) -> (
+    impl Iterator<Item = Event> + '_,
+    impl Iterator<Item = Event> + '_,
+) {
+    (
+        filter_polarity(events.iter().copied(), 1),
+        filter_polarity(events.iter().copied(), 0),
+    )
+}
+
+/// Keeps only the external trigger events of channel `id`
+pub fn filter_trigger_channel(
+    events: impl Iterator<Item = Event>,
+    id: u8,
+) -> impl Iterator<Item = Event> {
+    events.filter(
+        move |event| matches!(event, Event::ExternalTrigger { id: channel, .. } if *channel == id),
+    )
+}
+
+/// Groups the external trigger events by channel id as `(timestamp, polarity)`
+/// pairs, in stream order
+pub fn triggers_by_channel(events: impl Iterator<Item = Event>) -> HashMap<u8, Vec<(u64, u8)>> {
+    let mut channels: HashMap<u8, Vec<(u64, u8)>> = HashMap::new();
+    for event in events {
+        if let Event::ExternalTrigger { id, p, t } = event {
+            channels.entry(id).or_default().push((t, p));
+        }
+    }
+    channels
+}
+
+/// Interleaves two streams sorted by timestamp into a single sorted stream.
+///
+/// On equal timestamps the event of `a` comes first. Unknown events have no
+/// timestamp and are emitted as soon as they are reached in their stream.
+pub fn merge_by_time(
+    a: impl Iterator<Item = Event>,
+    b: impl Iterator<Item = Event>,
+) -> impl Iterator<Item = Event> {
+    a.merge_by(b, move |x, y| match (x.timestamp(), y.timestamp()) {
+        (None, _) => true,
+        (_, None) => false,
+        (Some(t_x), Some(t_y)) => t_x <= t_y,
+    })
+}
+
+/// What to do with a CD event whose timestamp goes backward
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonotonicMode {
+    /// Discard the event
+    Drop,
+    /// Bump the event timestamp to the last seen timestamp
+    Clamp,
+}
+
+/// Makes the CD event timestamps non-decreasing, as expected by [`crate::slice_events`].
+///
+/// Other events are passed through untouched.
+pub fn enforce_monotonic(
+    events: impl Iterator<Item = Event>,
+    mode: MonotonicMode,
+) -> impl Iterator<Item = Event> {
+    let mut last_t = 0;
+    events.filter_map(move |event| match event {
+        Event::CD { x, y, p, t } if t < last_t => match mode {
+            MonotonicMode::Drop => None,
+            MonotonicMode::Clamp => Some(Event::CD { x, y, p, t: last_t }),
+        },
+        Event::CD { t, .. } => {
+            last_t = t;
+            Some(event)
+        }
+        _ => Some(event),
+    })
+}
+
+/// Groups the events in `Vec`s of exactly `n` events, ignoring timestamps.
+///
+/// The last chunk may be shorter. Panics if `n` is 0.
+pub fn chunks(events: impl Iterator<Item = Event>, n: usize) -> impl Iterator<Item = Vec<Event>> {
+    assert!(n > 0, "chunk size must be non-zero");
+    let mut events = events;
+    std::iter::from_fn(move || {
+        let chunk: Vec<Event> = events.by_ref().take(n).collect();
+        (!chunk.is_empty()).then_some(chunk)
+    })
+}
+
+/// Same as [`chunks`] but only the CD events are kept
+pub fn cd_chunks(
+    events: impl Iterator<Item = Event>,
+    n: usize,
+) -> impl Iterator<Item = Vec<Event>> {
+    chunks(events.filter(Event::is_cd), n)
+}
+
+/// Keeps one CD event every `keep_every`, starting with the first one.
+///
+/// Only the CD events are decimated, triggers and other events are always
+/// kept. Coordinates and timestamps are left untouched. Panics if
+/// `keep_every` is 0.
+pub fn subsample(
+    events: impl Iterator<Item = Event>,
+    keep_every: usize,
+) -> impl Iterator<Item = Event> {
+    events.filter(subsample_predicate(keep_every))
+}
+
+/// Per-event test of [`subsample`], shared with [`crate::EventPipeline`]
+pub(crate) fn subsample_predicate(
+    keep_every: usize,
+) -> impl FnMut(&Event) -> bool + Send + 'static {
+    assert!(keep_every > 0, "keep_every must be non-zero");
+    let mut cd_index = 0;
+    move |event| {
+        if !event.is_cd() {
+            return true;
+        }
+        let keep = cd_index % keep_every == 0;
+        cd_index += 1;
+        keep
+    }
+}
+
+/// Keeps each CD event with probability `p`, drawn from a generator seeded
+/// with `seed` so that the output is reproducible.
+///
+/// As with [`subsample`], triggers and other events are always kept.
+pub fn subsample_fraction(
+    events: impl Iterator<Item = Event>,
+    p: f64,
+    seed: u64,
+) -> impl Iterator<Item = Event> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    events.filter(move |event| !event.is_cd() || rng.random_bool(p.clamp(0.0, 1.0)))
+}
+
+/// Drops the CD events whose pixel already fired less than `refractory_us`
+/// microseconds before.
+///
+/// The period is measured from the last kept event of the pixel. Events
+/// outside the `width * height` sensor and non CD events are passed through.
+pub fn refractory_filter(
+    events: impl Iterator<Item = Event>,
+    width: usize,
+    height: usize,
+    refractory_us: u64,
+) -> impl Iterator<Item = Event> {
+    events.filter(refractory_predicate(width, height, refractory_us))
+}
+
+/// Per-event test of [`refractory_filter`], shared with [`crate::EventPipeline`]
+pub(crate) fn refractory_predicate(
+    width: usize,
+    height: usize,
+    refractory_us: u64,
+) -> impl FnMut(&Event) -> bool + Send + 'static {
+    // u64::MAX marks the pixels that never fired
+    let mut last_timestamps = vec![u64::MAX; width * height];
+    move |event| {
+        let Event::CD { x, y, t, .. } = *event else {
+            return true;
+        };
+        let (x, y) = (x as usize, y as usize);
+        if x >= width || y >= height {
+            return true;
+        }
+        let last_t = &mut last_timestamps[y * width + x];
+        if *last_t != u64::MAX && t.saturating_sub(*last_t) < refractory_us {
+            return false;
+        }
+        *last_t = t;
+        true
+    }
+}
+
+/// Keeps the CD events inside the `[x0, x1) x [y0, y1)` region of interest,
+/// other events are passed through.
+///
+/// With `rebase`, the coordinates of the kept events are made relative to the
+/// `(x0, y0)` corner of the region.
+pub fn crop(
+    events: impl Iterator<Item = Event>,
+    x0: u16,
+    y0: u16,
+    x1: u16,
+    y1: u16,
+    rebase: bool,
+) -> impl Iterator<Item = Event> {
+    events.filter_map(crop_stage(x0, y0, x1, y1, rebase))
+}
+
+/// Per-event stage of [`crop`], shared with [`crate::EventPipeline`]
+pub(crate) fn crop_stage(
+    x0: u16,
+    y0: u16,
+    x1: u16,
+    y1: u16,
+    rebase: bool,
+) -> impl FnMut(Event) -> Option<Event> + Send + 'static {
+    move |event| match event {
+        Event::CD { x, y, p, t } => {
+            if !(x0..x1).contains(&x) || !(y0..y1).contains(&y) {
+                return None;
+            }
+            Some(match rebase {
+                true => Event::CD {
+                    x: x - x0,
+                    y: y - y0,
+                    p,
+                    t,
+                },
+                false => event,
+            })
+        }
+        _ => Some(event),
+    }
+}
+
+/// Mirrors the CD event coordinates along x and/or y on a `width * height`
+/// sensor, then swaps x and y when `transpose` is set, for recordings made
+/// with a flipped or rotated camera.
+///
+/// Transposed events are on a `height * width` sensor. Combined with
+/// `transpose`, `flip_y` rotates the image by 90° clockwise and `flip_x` by
+/// 90° counter-clockwise.
+///
+/// Events outside the sensor and non CD events are passed through.
+pub fn transform_coords(
+    events: impl Iterator<Item = Event>,
+    flip_x: bool,
+    flip_y: bool,
+    transpose: bool,
+    width: u16,
+    height: u16,
+) -> impl Iterator<Item = Event> {
+    events.map(move |event| match event {
+        Event::CD { x, y, p, t } if x < width && y < height => {
+            let x = if flip_x { width - 1 - x } else { x };
+            let y = if flip_y { height - 1 - y } else { y };
+            match transpose {
+                true => Event::CD { x: y, y: x, p, t },
+                false => Event::CD { x, y, p, t },
+            }
+        }
+        _ => event,
+    })
+}
+
+/// Snaps the timestamps of the CD and trigger events down to a multiple of
+/// `bin_us` microseconds.
+///
+/// A `bin_us` of 0 leaves the events untouched.
+pub fn downsample_time(
+    events: impl Iterator<Item = Event>,
+    bin_us: u64,
+) -> impl Iterator<Item = Event> {
+    let snap = move |t: u64| if bin_us == 0 { t } else { t - t % bin_us };
+    events.map(move |event| match event {
+        Event::CD { x, y, p, t } => Event::CD {
+            x,
+            y,
+            p,
+            t: snap(t),
+        },
+        Event::ExternalTrigger { id, p, t } => Event::ExternalTrigger { id, p, t: snap(t) },
+        Event::Unknown() => event,
+    })
+}
+
+/// Same as [`downsample_time`] but the CD events with the same `(x, y, p)`
+/// in a bin are collapsed into the first one.
+///
+/// The events are expected to be sorted by timestamp, as the duplicates are
+/// only looked for in the current bin.
+pub fn downsample_time_dedup(
+    events: impl Iterator<Item = Event>,
+    bin_us: u64,
+) -> impl Iterator<Item = Event> {
+    let mut current_bin = None;
+    let mut seen = HashSet::new();
+    downsample_time(events, bin_us).filter(move |event| {
+        let Event::CD { x, y, p, t } = *event else {
+            return true;
+        };
+        if current_bin != Some(t) {
+            current_bin = Some(t);
+            seen.clear();
+        }
+        seen.insert((x, y, p))
+    })
+}
+
+/// Pairs each event with the microseconds elapsed since the previous CD or
+/// trigger event, 0 for the first one.
+///
+/// Unknown events have no timestamp: they are paired with 0 and don't count
+/// as the previous event of the next one.
+pub fn with_inter_event_dt(
+    events: impl Iterator<Item = Event>,
+) -> impl Iterator<Item = (Event, u64)> {
+    let mut last_t = None;
+    events.map(move |event| {
+        let Some(t) = event.timestamp() else {
+            return (event, 0);
+        };
+        let dt = last_t.map_or(0, |last_t| t.saturating_sub(last_t));
+        last_t = Some(t);
+        (event, dt)
+    })
+}
+
+/// Sorts the runs of consecutive events sharing a timestamp, like the CD
+/// events of an EVT3 vector, by `y` then `x`. The triggers of a run come
+/// after its CD events, and events without a timestamp end the run.
+///
+/// Only the current run is buffered, so events of different timestamps are
+/// never reordered: the output is sorted by `(t, y, x)` when the timestamps
+/// of the input are, see [`enforce_monotonic`] otherwise.
+pub fn sorted_by_time(events: impl Iterator<Item = Event>) -> impl Iterator<Item = Event> {
+    let mut events = events.peekable();
+    let mut run = VecDeque::new();
+    std::iter::from_fn(move || {
+        if run.is_empty() {
+            let first = events.next()?;
+            let Some(t) = first.timestamp() else {
+                return Some(first);
+            };
+            run.push_back(first);
+            while let Some(event) = events.next_if(|event| event.timestamp() == Some(t)) {
+                run.push_back(event);
+            }
+            run.make_contiguous().sort_by_key(|event| match *event {
+                Event::CD { x, y, .. } => (false, y, x),
+                _ => (true, 0, 0),
+            });
+        }
+        run.pop_front()
+    })
+}
+
+/// Number of events between two calls of a progress callback, see [`report_progress`]
+pub const PROGRESS_INTERVAL: u64 = 1 << 16;
+
+/// Calls `progress(done, total)` every [`PROGRESS_INTERVAL`] events pulled
+/// from `events`, and once more at the end of the stream with the final
+/// count. `total` is only an estimate, like [`crate::RawFileReader::estimate_event_count`].
+pub fn report_progress<'a>(
+    events: impl Iterator<Item = Event> + 'a,
+    total: u64,
+    progress: &'a mut dyn FnMut(u64, u64),
+) -> impl Iterator<Item = Event> + 'a {
+    let mut events = events.fuse();
+    let mut done = 0;
+    let mut finished = false;
+    std::iter::from_fn(move || {
+        let event = events.next();
+        if event.is_some() {
+            done += 1;
+            if done % PROGRESS_INTERVAL == 0 {
+                progress(done, total);
+            }
+        } else if !finished {
+            finished = true;
+            progress(done, total);
+        }
+        event
+    })
+}
+
+/// Longest wait of [`replay_realtime`] between two events
+pub const MAX_REPLAY_GAP: Duration = Duration::from_secs(1);
+
+/// Emits the events at `speed` times the cadence of their timestamps, by
+/// sleeping until each CD or trigger event is due, to replay a recording as
+/// a live stream.
+///
+/// Gaps longer than [`MAX_REPLAY_GAP`] once scaled are shortened to it, so a
+/// pause in the recording doesn't stall the replay. Events without a
+/// timestamp are emitted right away.
+pub fn replay_realtime(
+    events: impl Iterator<Item = Event>,
+    speed: f64,
+) -> impl Iterator<Item = Event> {
+    assert!(speed > 0.0, "The replay speed must be positive");
+    // Wall clock time and timestamp of the first event
+    let mut origin: Option<(Instant, u64)> = None;
+    // Time removed from the long gaps
+    let mut skipped = Duration::ZERO;
+    events.inspect(move |event| {
+        let Some(t) = event.timestamp() else {
+            return;
+        };
+        let (start, t0) = *origin.get_or_insert((Instant::now(), t));
+        let elapsed = Duration::from_secs_f64(t.saturating_sub(t0) as f64 / 1e6 / speed);
+        let mut due = start + elapsed.saturating_sub(skipped);
+
+        let now = Instant::now();
+        if due > now + MAX_REPLAY_GAP {
+            skipped += due - (now + MAX_REPLAY_GAP);
+            due = now + MAX_REPLAY_GAP;
+        }
+        if due > now {
+            thread::sleep(due - now);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_polarity() {
+        let events: Vec<Event> = (0..100)
+            .map(|i| match i % 5 {
+                0 => Event::ExternalTrigger { id: 0, p: 1, t: i },
+                1 => Event::Unknown(),
+                _ => Event::CD {
+                    x: i as u16,
+                    y: 0,
+                    p: (i % 2) as u8,
+                    t: i,
+                },
+            })
+            .collect();
+
+        let (positive, negative) = split_polarity(&events);
+        let positive: Vec<Event> = positive.collect();
+        let negative: Vec<Event> = negative.collect();
+
+        assert!(positive
+            .iter()
+            .all(|e| e.polarity() == Some(1) && e.is_cd()));
+        assert!(negative
+            .iter()
+            .all(|e| e.polarity() == Some(0) && e.is_cd()));
+        let cd_count = events.iter().filter(|e| e.is_cd()).count();
+        assert_eq!(positive.len() + negative.len(), cd_count);
+    }
+
+    #[test]
+    fn test_triggers_by_channel() {
+        let events = [
+            Event::trigger(0, 1, 10),
+            Event::cd(0, 0, 1, 12),
+            Event::trigger(3, 1, 15),
+            Event::trigger(0, 0, 20),
+            Event::Unknown(),
+            Event::trigger(3, 0, 25),
+            Event::trigger(0, 1, 30),
+        ];
+
+        let channels = triggers_by_channel(events.into_iter());
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[&0], vec![(10, 1), (20, 0), (30, 1)]);
+        assert_eq!(channels[&3], vec![(15, 1), (25, 0)]);
+
+        let channel_3: Vec<Event> = filter_trigger_channel(events.into_iter(), 3).collect();
+        assert_eq!(
+            channel_3,
+            vec![Event::trigger(3, 1, 15), Event::trigger(3, 0, 25)]
+        );
+    }
+
+    #[test]
+    fn test_merge_by_time() {
+        let a: Vec<Event> = (0..100).map(|i| Event::cd(0, 0, 1, i * 3)).collect();
+        let b: Vec<Event> = (0..50)
+            .map(|i| match i % 10 {
+                0 => Event::trigger(0, 1, i * 6),
+                _ => Event::cd(1, 0, 0, i * 6),
+            })
+            .collect();
+
+        let merged: Vec<Event> = merge_by_time(a.iter().copied(), b.iter().copied()).collect();
+        assert_eq!(merged.len(), a.len() + b.len());
+        assert!(merged.iter().map(Event::timestamp).is_sorted());
+        // Ties are broken in favor of the first stream
+        assert_eq!(merged[0], Event::cd(0, 0, 1, 0));
+        assert_eq!(merged[1], Event::trigger(0, 1, 0));
+        assert!(a.iter().all(|e| merged.contains(e)));
+        assert!(b.iter().all(|e| merged.contains(e)));
+    }
+
+    #[test]
+    fn test_enforce_monotonic() {
+        let events = [
+            Event::cd(0, 0, 1, 10),
+            Event::cd(1, 0, 1, 20),
+            Event::cd(2, 0, 1, 15),
+            Event::trigger(0, 1, 5),
+            Event::cd(3, 0, 1, 20),
+            Event::cd(4, 0, 1, 0),
+            Event::cd(5, 0, 1, 30),
+        ];
+        let is_sorted = |events: &[Event]| {
+            events
+                .iter()
+                .filter(|e| e.is_cd())
+                .filter_map(|e| e.timestamp())
+                .collect::<Vec<_>>()
+                .is_sorted()
+        };
+
+        let dropped: Vec<Event> =
+            enforce_monotonic(events.into_iter(), MonotonicMode::Drop).collect();
+        assert!(is_sorted(&dropped));
+        assert_eq!(dropped.len(), 5);
+        assert!(!dropped.iter().any(|e| e.x() == Some(2) || e.x() == Some(4)));
+
+        let clamped: Vec<Event> =
+            enforce_monotonic(events.into_iter(), MonotonicMode::Clamp).collect();
+        assert!(is_sorted(&clamped));
+        assert_eq!(clamped.len(), events.len());
+        assert_eq!(clamped[2], Event::cd(2, 0, 1, 20));
+        assert_eq!(clamped[5], Event::cd(4, 0, 1, 20));
+    }
+
+    #[test]
+    fn test_chunks() {
+        let events = (0..1000).map(|i| Event::cd(0, 0, 1, i));
+        let sizes: Vec<usize> = chunks(events, 256).map(|chunk| chunk.len()).collect();
+        assert_eq!(sizes, vec![256, 256, 256, 232]);
+
+        let events = (0..10).map(|i| match i % 2 {
+            0 => Event::trigger(0, 1, i),
+            _ => Event::cd(0, 0, 1, i),
+        });
+        let cd: Vec<Vec<Event>> = cd_chunks(events, 4).collect();
+        assert_eq!(cd.len(), 2);
+        assert!(cd.iter().flatten().all(|e| e.is_cd()));
+    }
+
+    #[test]
+    fn test_subsample() {
+        let events: Vec<Event> = (0..1002)
+            .map(|i| match i % 100 {
+                0 => Event::trigger(0, 1, i),
+                _ => Event::cd(0, 0, 1, i),
+            })
+            .collect();
+        let cd_count = events.iter().filter(|e| e.is_cd()).count();
+        assert_eq!(cd_count, 991);
+
+        let kept: Vec<Event> = subsample(events.iter().copied(), 4).collect();
+        assert_eq!(
+            kept.iter().filter(|e| e.is_cd()).count(),
+            cd_count.div_ceil(4)
+        );
+        assert_eq!(kept.iter().filter(|e| e.is_trigger()).count(), 11);
+
+        let fraction: Vec<Event> = subsample_fraction(events.iter().copied(), 0.25, 42).collect();
+        let again: Vec<Event> = subsample_fraction(events.iter().copied(), 0.25, 42).collect();
+        assert_eq!(fraction, again);
+        let fraction_cd = fraction.iter().filter(|e| e.is_cd()).count();
+        assert!((150..350).contains(&fraction_cd));
+        assert_eq!(fraction.iter().filter(|e| e.is_trigger()).count(), 11);
+    }
+
+    #[test]
+    fn test_refractory_filter() {
+        let events = [
+            Event::cd(1, 1, 1, 100),
+            Event::cd(0, 0, 1, 102),
+            Event::cd(1, 1, 0, 105),
+            Event::trigger(0, 1, 106),
+            Event::cd(1, 1, 1, 110),
+            Event::cd(1, 1, 1, 115),
+        ];
+        let kept: Vec<Event> = refractory_filter(events.into_iter(), 2, 2, 10).collect();
+        assert_eq!(
+            kept,
+            vec![
+                Event::cd(1, 1, 1, 100),
+                Event::cd(0, 0, 1, 102),
+                Event::trigger(0, 1, 106),
+                Event::cd(1, 1, 1, 110),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_crop() {
+        let events = [
+            Event::cd(0, 5, 1, 100),
+            Event::cd(2, 3, 1, 101),
+            Event::trigger(0, 1, 102),
+            Event::cd(9, 3, 0, 103),
+            Event::cd(8, 9, 0, 104),
+        ];
+        let cropped: Vec<Event> = crop(events.into_iter(), 2, 3, 9, 9, false).collect();
+        assert_eq!(
+            cropped,
+            vec![Event::cd(2, 3, 1, 101), Event::trigger(0, 1, 102)]
+        );
+
+        let rebased: Vec<Event> = crop(events.into_iter(), 2, 3, 10, 10, true).collect();
+        assert_eq!(
+            rebased,
+            vec![
+                Event::cd(0, 0, 1, 101),
+                Event::trigger(0, 1, 102),
+                Event::cd(7, 0, 0, 103),
+                Event::cd(6, 6, 0, 104),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transform_coords() {
+        let events = [
+            Event::cd(0, 0, 1, 100),
+            Event::cd(3, 2, 0, 101),
+            Event::trigger(1, 1, 102),
+            Event::cd(9, 9, 1, 103),
+        ];
+        let flipped: Vec<Event> =
+            transform_coords(events.into_iter(), false, true, false, 4, 3).collect();
+        assert_eq!(
+            flipped,
+            vec![
+                Event::cd(0, 2, 1, 100),
+                Event::cd(3, 0, 0, 101),
+                Event::trigger(1, 1, 102),
+                Event::cd(9, 9, 1, 103),
+            ]
+        );
+
+        let rotated: Vec<Event> =
+            transform_coords(events.into_iter(), true, true, false, 4, 3).collect();
+        assert_eq!(rotated[0], Event::cd(3, 2, 1, 100));
+        assert_eq!(rotated[1], Event::cd(0, 0, 0, 101));
+
+        // 90° clockwise, the 4x3 sensor becomes 3x4: the top left corner goes
+        // to the top right and the bottom right one to the bottom left
+        let rotated: Vec<Event> =
+            transform_coords(events.into_iter(), false, true, true, 4, 3).collect();
+        assert_eq!(
+            rotated,
+            vec![
+                Event::cd(2, 0, 1, 100),
+                Event::cd(0, 3, 0, 101),
+                Event::trigger(1, 1, 102),
+                Event::cd(9, 9, 1, 103),
+            ]
+        );
+        let transposed: Vec<Event> = transform_coords(
+            [Event::cd(3, 1, 1, 0)].into_iter(),
+            false,
+            false,
+            true,
+            4,
+            3,
+        )
+        .collect();
+        assert_eq!(transposed, vec![Event::cd(1, 3, 1, 0)]);
+    }
+
+    #[test]
+    fn test_downsample_time() {
+        let events = [
+            Event::cd(0, 0, 1, 1003),
+            Event::cd(1, 0, 1, 1500),
+            Event::trigger(0, 1, 1999),
+            Event::cd(0, 0, 1, 1999),
+            Event::cd(0, 0, 0, 2000),
+            Event::cd(0, 0, 1, 2001),
+        ];
+
+        let binned: Vec<Event> = downsample_time(events.into_iter(), 1000).collect();
+        assert_eq!(
+            binned
+                .iter()
+                .filter_map(Event::timestamp)
+                .collect::<Vec<_>>(),
+            vec![1000, 1000, 1000, 1000, 2000, 2000]
+        );
+        assert_eq!(binned[1], Event::cd(1, 0, 1, 1000));
+
+        let deduplicated: Vec<Event> = downsample_time_dedup(events.into_iter(), 1000).collect();
+        assert_eq!(
+            deduplicated,
+            vec![
+                Event::cd(0, 0, 1, 1000),
+                Event::cd(1, 0, 1, 1000),
+                Event::trigger(0, 1, 1000),
+                Event::cd(0, 0, 0, 2000),
+                Event::cd(0, 0, 1, 2000),
+            ]
+        );
+
+        let untouched: Vec<Event> = downsample_time(events.into_iter(), 0).collect();
+        assert_eq!(untouched, events);
+    }
+
+    #[test]
+    fn test_report_progress() {
+        let events = (0..PROGRESS_INTERVAL * 2 + 10).map(|t| Event::cd(0, 0, 1, t));
+        let mut calls = Vec::new();
+        let mut progress = |done, total| calls.push((done, total));
+        let count = report_progress(events, 1000, &mut progress).count();
+        assert_eq!(count as u64, PROGRESS_INTERVAL * 2 + 10);
+        assert_eq!(
+            calls,
+            [
+                (PROGRESS_INTERVAL, 1000),
+                (PROGRESS_INTERVAL * 2, 1000),
+                (PROGRESS_INTERVAL * 2 + 10, 1000)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_realtime() {
+        // 200ms of events replayed 10 times faster
+        let events: Vec<Event> = (0..=100).map(|i| Event::cd(0, 0, 1, i * 2_000)).collect();
+        let start = Instant::now();
+        let replayed: Vec<Event> = replay_realtime(events.iter().copied(), 10.0).collect();
+        let elapsed = start.elapsed();
+
+        assert_eq!(replayed, events);
+        assert!(elapsed >= Duration::from_millis(20), "{elapsed:?}");
+        assert!(elapsed < Duration::from_millis(200), "{elapsed:?}");
+    }
+
+    #[test]
+    fn test_with_inter_event_dt() {
+        let events = [
+            Event::cd(0, 0, 1, 100),
+            Event::cd(1, 0, 1, 150),
+            Event::Unknown(),
+            Event::cd(2, 0, 0, 150),
+            Event::trigger(0, 1, 400),
+            Event::cd(3, 0, 1, 410),
+        ];
+        let deltas: Vec<u64> = with_inter_event_dt(events.into_iter())
+            .map(|(_, dt)| dt)
+            .collect();
+        assert_eq!(deltas, [0, 50, 0, 0, 250, 10]);
+    }
+
+    #[test]
+    fn test_sorted_by_time() {
+        let events: Vec<Event> =
+            crate::RawFileReader::new(std::path::Path::new("data/openeb/gen4_evt3_hand.raw"))
+                .expect("Failed to open test file")
+                .read_events()
+                .filter(Event::is_cd)
+                .take(20_000)
+                .collect();
+        let key = |event: &Event| event.as_cd().map(|(x, y, _, t)| (t, y, x));
+        assert!(!events.is_sorted_by_key(key));
+
+        let sorted: Vec<Event> = sorted_by_time(events.iter().copied()).collect();
+        assert!(sorted.is_sorted_by_key(key));
+        let mut expected = events.clone();
+        expected.sort_by_key(key);
+        assert_eq!(sorted, expected);
+
+        let events = [
+            Event::trigger(0, 1, 100),
+            Event::cd(2, 1, 1, 100),
+            Event::cd(1, 1, 1, 100),
+            Event::Unknown(),
+            Event::cd(0, 5, 1, 100),
+            Event::cd(0, 0, 1, 50),
+        ];
+        assert_eq!(
+            sorted_by_time(events.into_iter()).collect::<Vec<Event>>(),
+            [
+                Event::cd(1, 1, 1, 100),
+                Event::cd(2, 1, 1, 100),
+                Event::trigger(0, 1, 100),
+                Event::Unknown(),
+                Event::cd(0, 5, 1, 100),
+                Event::cd(0, 0, 1, 50),
+            ]
+        );
+    }
+}
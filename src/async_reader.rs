@@ -0,0 +1,151 @@
+use crate::{
+    DecodeError, Event, RawFileHeader, RawFileReaderBuilder, RawFileReaderError, ReaderOptions,
+};
+use futures_core::Stream;
+use std::{
+    path::Path,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+use tokio::{sync::mpsc, task};
+
+// Events sent at once by the decoding task
+const BATCH_SIZE: usize = 4096;
+// Batches decoded ahead of the stream before the decoding task waits
+const QUEUED_BATCHES: usize = 4;
+
+/// Reader of a raw file for async runtimes, yielding the events as a
+/// [`Stream`].
+///
+/// The file is decoded by a [`crate::RawFileReader`] on tokio's blocking
+/// thread pool, which sends the events to the stream in batches. Polling the
+/// stream never decodes, so it doesn't stall the runtime. Dropping the stream
+/// stops the decoding.
+pub struct AsyncRawFileReader {
+    pub header: RawFileHeader,
+    batches: mpsc::Receiver<Result<Vec<Event>, DecodeError>>,
+    batch: std::vec::IntoIter<Event>,
+    error: Option<DecodeError>,
+}
+
+impl AsyncRawFileReader {
+    pub async fn new(path: &Path) -> Result<Self, RawFileReaderError> {
+        Self::with_options(path, ReaderOptions::default()).await
+    }
+
+    /// Opens the file with the decoding options of a [`crate::RawFileReader`]
+    pub async fn with_options(
+        path: &Path,
+        options: ReaderOptions,
+    ) -> Result<Self, RawFileReaderError> {
+        let builder = RawFileReaderBuilder {
+            path: path.into(),
+            options,
+        };
+        let reader = task::spawn_blocking(move || builder.build())
+            .await
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic.into_panic()))?;
+        let header = reader.header.clone();
+
+        let (sender, batches) = mpsc::channel(QUEUED_BATCHES);
+        task::spawn_blocking(move || {
+            let mut events = reader.into_iter();
+            loop {
+                let batch: Vec<Event> = events.by_ref().take(BATCH_SIZE).collect();
+                if batch.is_empty() {
+                    break;
+                }
+                // The stream was dropped
+                if sender.blocking_send(Ok(batch)).is_err() {
+                    return;
+                }
+            }
+            if let Some(error) = events.decode_error() {
+                let _ = sender.blocking_send(Err(error));
+            }
+        });
+
+        Ok(AsyncRawFileReader {
+            header,
+            batches,
+            batch: Vec::new().into_iter(),
+            error: None,
+        })
+    }
+
+    /// Decoding error that ended the stream early, if any. Only happens with
+    /// [`crate::UnknownPolicy::Error`].
+    pub fn error(&self) -> Option<DecodeError> {
+        self.error
+    }
+}
+
+impl Stream for AsyncRawFileReader {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Event>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.batch.next() {
+                return Poll::Ready(Some(event));
+            }
+            match ready!(this.batches.poll_recv(cx)) {
+                Some(Ok(batch)) => this.batch = batch.into_iter(),
+                Some(Err(error)) => this.error = Some(error),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{synthetic_evt3, RawFileReader, UnknownPolicy};
+    use futures_util::StreamExt;
+
+    #[tokio::test]
+    async fn test_async_reader() {
+        for file in [
+            "data/openeb/gen4_evt3_hand.raw",
+            "data/openeb/claque_doigt_evt21.raw",
+            "data/openeb/blinking_leds.raw",
+        ] {
+            let path = Path::new(file);
+            let expected: Vec<Event> = RawFileReader::new(path)
+                .expect("Failed to open test file")
+                .read_events()
+                .collect();
+
+            let reader = AsyncRawFileReader::new(path)
+                .await
+                .expect("Failed to open test file");
+            let events: Vec<Event> = reader.collect().await;
+            assert_eq!(events.len(), expected.len());
+            assert_eq!(events, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_reader_options() {
+        let mut expected: Vec<Event> = (0..2 * BATCH_SIZE as u64)
+            .map(|t| Event::cd((t % 100) as u16, 5, 1, t))
+            .collect();
+        expected.insert(10, Event::trigger(0, 1, 10));
+        let path = std::env::temp_dir().join(format!("libreeb_async_{}.raw", std::process::id()));
+        std::fs::write(&path, synthetic_evt3(&expected)).expect("Failed to write test file");
+
+        let options = ReaderOptions {
+            cd_only: true,
+            unknown_policy: UnknownPolicy::Error,
+            ..ReaderOptions::default()
+        };
+        let reader = AsyncRawFileReader::with_options(&path, options)
+            .await
+            .expect("Failed to open test file");
+        let events: Vec<Event> = reader.collect().await;
+        expected.remove(10);
+        assert_eq!(events, expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{
+    declare_raw_evt, parse_dimension, parse_geometry, ByteSource, CameraGeometry, Event,
+    EventDecoder, EvtReader, RawFileReaderError,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+use zerocopy::{FromBytes, Immutable, KnownLayout};
+
+// DAT CD records definition, the layout of the 8 bytes little endian record is:
+//
+// 63    61 60 59          46 45          32 31                0
+// +-------+--+--------------+--------------+------------------+
+// |unused |p |      y       |      x       |    timestamp     |
+// +-------+--+--------------+--------------+------------------+
+declare_raw_evt! {
+    pub struct Dat(u64);
+    timestamp(u64): 31, 0;
+    x(u16): 45, 32;
+    y(u16): 59, 46;
+    p(u8): 60, 60;
+}
+
+// Record types holding CD events
+const DAT_EVENT_2D: u8 = 0x00;
+const DAT_EVENT_CD: u8 = 0x0C;
+const DAT_RECORD_SIZE: u8 = 8;
+
+const TIMESTAMP_LOOP: u64 = 1 << 32;
+
+/// Header of a DAT file
+#[derive(Debug, Clone)]
+pub struct DatFileHeader {
+    pub header_dict: HashMap<String, String>,
+    pub camera_geometry: CameraGeometry,
+    /// Type of the records, as written after the `%` header
+    pub event_type: u8,
+    /// Size of the records in bytes
+    pub event_size: u8,
+}
+
+/// Parses the `%` header of a DAT file and the record type and size that
+/// follow it, leaving `reader` at the start of the records
+pub fn parse_dat_header(reader: &mut impl BufRead) -> Result<DatFileHeader, RawFileReaderError> {
+    let mut header_dict: HashMap<String, String> = HashMap::new();
+    let mut camera_geometry = CameraGeometry {
+        width: 0,
+        height: 0,
+    };
+
+    loop {
+        let buffer = reader
+            .fill_buf()
+            .map_err(|_e| RawFileReaderError::ReadBytesFailed)?;
+        let next_char = buffer.first().ok_or(RawFileReaderError::ReadBytesFailed)?;
+        if *next_char != b'%' {
+            break;
+        }
+
+        let mut header_line = String::new();
+        reader
+            .read_line(&mut header_line)
+            .map_err(|_e| RawFileReaderError::ReadBytesFailed)?;
+
+        let mut parts = header_line.trim_start_matches('%').trim().splitn(2, ' ');
+        let key = parts.next().ok_or(RawFileReaderError::ParseHeaderFailed)?;
+        if let Some(value) = parts.next() {
+            match key {
+                "Width" => camera_geometry.width = parse_dimension(value)?,
+                "Height" => camera_geometry.height = parse_dimension(value)?,
+                "geometry" => camera_geometry = parse_geometry(value)?,
+                _ => {}
+            }
+            header_dict.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let mut record_info = [0u8; 2];
+    reader
+        .read_exact(&mut record_info)
+        .map_err(|_e| RawFileReaderError::ReadBytesFailed)?;
+    let [event_type, event_size] = record_info;
+
+    Ok(DatFileHeader {
+        header_dict,
+        camera_geometry,
+        event_type,
+        event_size,
+    })
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct DatDecoder {
+    last_timestamp: u64,
+    time_loops: u64,
+}
+
+impl EventDecoder for DatDecoder {
+    type RawEventType = Dat;
+
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn format_name() -> &'static str {
+        "DAT"
+    }
+
+    fn decode(&mut self, raw_event: &[Self::RawEventType], event_queue: &mut VecDeque<Event>) {
+        for evt in raw_event {
+            // Timestamps are stored on 32 bits and loop every ~71 minutes
+            let timestamp = evt.timestamp();
+            if timestamp + TIMESTAMP_LOOP / 2 < self.last_timestamp {
+                self.time_loops += 1;
+            }
+            self.last_timestamp = timestamp;
+
+            event_queue.push_back(Event::CD {
+                x: evt.x(),
+                y: evt.y(),
+                p: evt.p(),
+                t: self.time_loops * TIMESTAMP_LOOP + timestamp,
+            });
+        }
+    }
+}
+
+/// Reader of the CD events of a DAT file, the older Prophesee file format
+pub struct DatFileReader {
+    pub header: DatFileHeader,
+    path: Option<Box<Path>>, // None when the source isn't a file
+    event_iterator: EvtReader<ByteSource, DatDecoder>,
+}
+
+impl DatFileReader {
+    pub fn new(path: &Path) -> Result<Self, RawFileReaderError> {
+        let file =
+            File::open(path).map_err(|e| RawFileReaderError::FileOpenError(path.into(), e))?;
+        let mut reader = Self::from_bufread(BufReader::with_capacity(64 * 1024, file))?;
+        reader.path = Some(path.into());
+        Ok(reader)
+    }
+
+    /// Creates a reader decoding the DAT data from any buffered source
+    pub fn from_bufread(
+        reader: impl BufRead + Send + Sync + 'static,
+    ) -> Result<Self, RawFileReaderError> {
+        let mut reader: ByteSource = Box::new(reader);
+        let header = parse_dat_header(&mut reader)?;
+        if !matches!(header.event_type, DAT_EVENT_2D | DAT_EVENT_CD) {
+            return Err(RawFileReaderError::UnsupportedEventType(format!(
+                "DAT record type {:#x}",
+                header.event_type
+            )));
+        }
+        if header.event_size != DAT_RECORD_SIZE {
+            return Err(RawFileReaderError::UnsupportedEventType(format!(
+                "DAT record size {}",
+                header.event_size
+            )));
+        }
+
+        Ok(DatFileReader {
+            header,
+            path: None,
+            event_iterator: EvtReader::new(reader, DatDecoder::new()),
+        })
+    }
+
+    pub fn read_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        &mut self.event_iterator
+    }
+
+    /// Resets the file reader, which fails if the source isn't a file
+    pub fn reset(&mut self) -> Result<(), RawFileReaderError> {
+        let Some(path) = &self.path else {
+            return Err(RawFileReaderError::SourceNotReopenable);
+        };
+        *self = Self::new(path)?;
+        Ok(())
+    }
+}
+
+impl IntoIterator for DatFileReader {
+    type Item = Event;
+    type IntoIter = EvtReader<ByteSource, DatDecoder>;
+
+    /// Consumes the reader, yielding its remaining events
+    fn into_iter(self) -> Self::IntoIter {
+        self.event_iterator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(x: u32, y: u32, p: u32, t: u32) -> [u8; 8] {
+        let data = x | (y << 14) | (p << 28);
+        let mut bytes = [0; 8];
+        bytes[..4].copy_from_slice(&t.to_le_bytes());
+        bytes[4..].copy_from_slice(&data.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_dat_decoder() {
+        let mut bytes = b"% Data file containing CD events.\n\
+            % Version 2\n\
+            % Height 480\n\
+            % Width 640\n"
+            .to_vec();
+        bytes.extend([DAT_EVENT_CD, DAT_RECORD_SIZE]);
+        bytes.extend(record(12, 34, 1, 100));
+        bytes.extend(record(639, 479, 0, 150));
+        bytes.extend(record(0, 0, 1, u32::MAX));
+        bytes.extend(record(1, 2, 0, 10));
+
+        let mut reader =
+            DatFileReader::from_bufread(std::io::Cursor::new(bytes)).expect("Failed to parse");
+        assert_eq!(
+            reader.header.camera_geometry,
+            CameraGeometry {
+                width: 640,
+                height: 480
+            }
+        );
+        assert_eq!(reader.header.header_dict["Version"], "2");
+
+        let events: Vec<Event> = reader.read_events().collect();
+        assert_eq!(
+            events,
+            vec![
+                Event::cd(12, 34, 1, 100),
+                Event::cd(639, 479, 0, 150),
+                Event::cd(0, 0, 1, u32::MAX as u64),
+                Event::cd(1, 2, 0, TIMESTAMP_LOOP + 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_dat_record() {
+        let mut bytes = b"% Version 2\n".to_vec();
+        bytes.extend([0x0E, DAT_RECORD_SIZE]);
+        assert!(matches!(
+            DatFileReader::from_bufread(std::io::Cursor::new(bytes)),
+            Err(RawFileReaderError::UnsupportedEventType(_))
+        ));
+    }
+}
@@ -4,7 +4,10 @@ use crossterm::{
     ExecutableCommand,
 };
 use itertools::Itertools;
-use libreeb::{slice_events, Event, RawFileReader, SliceBy};
+use libreeb::{
+    accumulate_frame, save_frame_png, slice_events, split_polarity, Event, EventSlice,
+    RawFileReader, SliceBy,
+};
 use ratatui::{
     crossterm::event::{self, KeyCode, MouseEventKind},
     layout::{Alignment, Constraint, Layout, Position, Rect},
@@ -71,10 +74,61 @@ struct App {
     fps: f64,
     pause: bool,
     step: bool,
+    playback_multiplier: f64,
+    slice_cache: SliceCache,
+    sensor_width: f64,
+    sensor_height: f64,
+}
+
+/// Result of a [`SliceCache::tick`]
+#[derive(Debug, PartialEq, Eq)]
+enum TickOutcome {
+    /// Paused, the cached slice is kept
+    Kept,
+    /// A new slice was pulled
+    Advanced,
+    /// A new slice was needed but the stream is exhausted
+    EndOfStream,
+}
+
+/// Keeps the slice currently on screen so that pausing redraws it without
+/// consuming new events from the reader
+#[derive(Default)]
+struct SliceCache {
+    slice: EventSlice,
 }
 
+impl SliceCache {
+    /// Pulls the next slice when playing or stepping, `next_slice` is not
+    /// called at all while paused
+    fn tick(
+        &mut self,
+        pause: bool,
+        step: bool,
+        next_slice: impl FnOnce() -> Option<EventSlice>,
+    ) -> TickOutcome {
+        if pause && !step {
+            return TickOutcome::Kept;
+        }
+        match next_slice() {
+            Some(slice) => {
+                self.slice = slice;
+                TickOutcome::Advanced
+            }
+            None => TickOutcome::EndOfStream,
+        }
+    }
+}
+
+const MIN_PLAYBACK_MULTIPLIER: f64 = 1.0 / 64.0;
+const MAX_PLAYBACK_MULTIPLIER: f64 = 64.0;
+// Sensor size used when the file header doesn't provide the geometry
+const DEFAULT_SENSOR_SIZE: u32 = 320;
+
 impl App {
     fn new(file_reader: RawFileReader) -> Self {
+        let geometry = file_reader.header.camera_geometry;
+        let or_default = |size: u32| if size == 0 { DEFAULT_SENSOR_SIZE } else { size };
         Self {
             exit: false,
             x: 0.0,
@@ -86,10 +140,14 @@ impl App {
             is_drawing: false,
             file_reader,
             current_timetamp: 0,
-            slice_duration: 1_000,
+            slice_duration: 2_000,
             fps: 0.0,
             pause: false,
             step: false,
+            playback_multiplier: 1.0,
+            slice_cache: SliceCache::default(),
+            sensor_width: f64::from(or_default(geometry.width)),
+            sensor_height: f64::from(or_default(geometry.height)),
         }
     }
 
@@ -125,6 +183,15 @@ impl App {
             KeyCode::Char('q') => self.exit = true,
             KeyCode::Char('p') => self.pause = !self.pause,
             KeyCode::Char('s') => self.step = true,
+            KeyCode::Char('c') => self.save_snapshot(),
+            KeyCode::Char('+') => {
+                self.playback_multiplier =
+                    (self.playback_multiplier * 2.0).min(MAX_PLAYBACK_MULTIPLIER)
+            }
+            KeyCode::Char('-') => {
+                self.playback_multiplier =
+                    (self.playback_multiplier / 2.0).max(MIN_PLAYBACK_MULTIPLIER)
+            }
             KeyCode::Down | KeyCode::Char('j') => self.y += 1.0,
             KeyCode::Up | KeyCode::Char('k') => self.y -= 1.0,
             KeyCode::Right | KeyCode::Char('l') => self.x += 1.0,
@@ -133,6 +200,19 @@ impl App {
         }
     }
 
+    /// Saves the slice on screen as `snapshot_<timestamp>.png` in the current
+    /// directory
+    fn save_snapshot(&self) {
+        let frame = accumulate_frame(
+            self.slice_cache.slice.iter().copied(),
+            self.sensor_width as usize,
+            self.sensor_height as usize,
+        );
+        let path = format!("snapshot_{}.png", self.current_timetamp);
+        // There's no room to report the error in the TUI, a failed snapshot is skipped
+        let _ = save_frame_png(&frame, Path::new(&path));
+    }
+
     fn handle_mouse_event(&mut self, event: event::MouseEvent) {
         match event.kind {
             MouseEventKind::Down(_) => self.is_drawing = true,
@@ -145,46 +225,47 @@ impl App {
     }
 
     fn on_tick(&mut self) {
-        // Do do anything if we are in pause
-        if self.pause && !self.step {
-            return;
-        }
         // let data = self.file_reader.read_events().take(4048 * 2).collect_vec();
-        let data = slice_events(self.file_reader.read_events(), SliceBy::Time(2000)).next();
-
-        if let Some(mut data) = data {
-            // Keep only cd events (for now) TODO: Maybe handle external triggers
-            data.retain(|e| matches!(e, Event::CD { .. }));
-
-            self.current_timetamp = data.first().unwrap().timestamp().unwrap();
-
-            self.positive_points = data
-                .iter()
-                .filter(|evt| evt.polarity().unwrap() == 1)
-                .map(|evt| Position {
-                    x: evt.x().unwrap(),
-                    y: evt.y().unwrap(),
-                })
-                .collect_vec();
-
-            self.negative_points = data
-                .iter()
-                .filter(|evt| evt.polarity().unwrap() == 0)
-                .map(|evt| Position {
-                    x: evt.x().unwrap(),
-                    y: evt.y().unwrap(),
-                })
-                .collect_vec();
-        } else {
-            self.file_reader.reset();
+        // Microseconds of events consumed per frame
+        let slice_length = ((self.slice_duration as f64 * self.playback_multiplier) as u64).max(1);
+        let file_reader = &mut self.file_reader;
+        let outcome = self.slice_cache.tick(self.pause, self.step, || {
+            slice_events(file_reader.read_events(), SliceBy::Time(slice_length)).next()
+        });
+        self.step = false;
+
+        match outcome {
+            TickOutcome::Kept => return,
+            TickOutcome::Advanced => self.update_points(),
+            TickOutcome::EndOfStream => self.file_reader.reset().unwrap(),
         }
         self.tick_count += 1;
-        self.step = false;
+    }
+
+    fn update_points(&mut self) {
+        // Keep only cd events (for now) TODO: Maybe handle external triggers
+        let slice = &mut self.slice_cache.slice;
+        self.current_timetamp = slice.start_us;
+
+        let data = &mut slice.events;
+        data.retain(Event::is_cd);
+
+        let to_position = |(x, y)| Position { x, y };
+        let (positive, negative) = split_polarity(data);
+        self.positive_points = positive
+            .filter_map(|evt| evt.coords())
+            .map(to_position)
+            .collect_vec();
+        self.negative_points = negative
+            .filter_map(|evt| evt.coords())
+            .map(to_position)
+            .collect_vec();
     }
 
     fn draw(&self, frame: &mut Frame) {
-        let horizontal = Layout::horizontal([Constraint::Length(320), Constraint::Length(320)]);
-        let vertical = Layout::vertical([Constraint::Length(320), Constraint::Length(320)]);
+        let horizontal =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]);
+        let vertical = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)]);
         let [left, right] = horizontal.areas(frame.area());
         let [draw, map] = vertical.areas(left);
         let [pong, boxes] = vertical.areas(right);
@@ -211,20 +292,14 @@ impl App {
         Canvas::default()
             .block(Block::bordered().title("Event Slices"))
             .marker(self.marker)
-            .x_bounds([0.0, 1.0 /*f64::from(area.width)*/])
-            .y_bounds([0.0, 1.0 /*f64::from(area.height)*/])
+            .x_bounds([0.0, self.sensor_width])
+            .y_bounds([0.0, self.sensor_height])
             .paint(move |ctx| {
-                let ppoints = self
-                    .positive_points
-                    .iter()
-                    .map(|p| (p.x as f64 / 320.0, 1.0 - (p.y as f64 / 320.0)))
-                    .collect_vec();
-
-                let npoints = self
-                    .negative_points
-                    .iter()
-                    .map(|p| (p.x as f64 / 320.0, 1.0 - (p.y as f64 / 320.0)))
-                    .collect_vec();
+                // The sensor origin is top left while the canvas one is bottom left
+                let to_canvas =
+                    |p: &Position| (f64::from(p.x), self.sensor_height - f64::from(p.y));
+                let ppoints = self.positive_points.iter().map(to_canvas).collect_vec();
+                let npoints = self.negative_points.iter().map(to_canvas).collect_vec();
 
                 ctx.draw(&Points {
                     coords: &ppoints,
@@ -252,10 +327,13 @@ impl App {
             time.microsecond() / 1000
         );
 
-        Paragraph::new(format!("Timestamp: {}\n FPS {:.1}", timestamp, self.fps))
-            .block(Block::bordered().title("Info"))
-            .alignment(Alignment::Left)
-            .wrap(Wrap { trim: true })
+        Paragraph::new(format!(
+            "Timestamp: {}\n FPS {:.1}\n Speed x{}",
+            timestamp, self.fps, self.playback_multiplier
+        ))
+        .block(Block::bordered().title("Info"))
+        .alignment(Alignment::Left)
+        .wrap(Wrap { trim: true })
     }
 
     fn boxes_canvas(&self, area: Rect) -> impl Widget {
@@ -297,6 +375,41 @@ impl App {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice_cache() {
+        let mut cache = SliceCache::default();
+        let mut pulled = 0;
+        let mut next_slice = || {
+            pulled += 1;
+            Some(vec![Event::cd(0, 0, 1, pulled)].into())
+        };
+
+        assert_eq!(
+            cache.tick(false, false, &mut next_slice),
+            TickOutcome::Advanced
+        );
+        assert_eq!(cache.tick(true, false, &mut next_slice), TickOutcome::Kept);
+        assert_eq!(cache.tick(true, false, &mut next_slice), TickOutcome::Kept);
+        assert_eq!(cache.slice.events, vec![Event::cd(0, 0, 1, 1)]);
+
+        // Stepping while paused consumes exactly one slice
+        assert_eq!(
+            cache.tick(true, true, &mut next_slice),
+            TickOutcome::Advanced
+        );
+        assert_eq!(cache.tick(true, false, &mut next_slice), TickOutcome::Kept);
+        assert_eq!(cache.slice.events, vec![Event::cd(0, 0, 1, 2)]);
+
+        assert_eq!(cache.tick(false, false, || None), TickOutcome::EndOfStream);
+        assert_eq!(cache.slice.events, vec![Event::cd(0, 0, 1, 2)]);
+        assert_eq!(pulled, 2);
+    }
+}
+
 /*
 #![feature(test)]
 
@@ -1,5 +1,5 @@
 use crate::declare_raw_evt;
-use crate::{Event, EventDecoder};
+use crate::{DecodeError, Event, EventDecoder, RawWord, UnknownPolicy};
 use std::collections::VecDeque;
 use zerocopy::{FromBytes, Immutable, KnownLayout};
 
@@ -21,10 +21,20 @@ declare_raw_evt! {
     trigger_value(u8): 1, 0;
 }
 
+impl RawWord for Evt2 {
+    fn raw(&self) -> u64 {
+        u64::from(self.data)
+    }
+
+    fn word_type(&self) -> u8 {
+        self.event_type()
+    }
+}
+
 const NUM_BITS_IN_TIMESTAMP_LSB: u64 = 6;
-const _MAX_TIMESTAMP: u64 = ((1 << 28) - 1) << NUM_BITS_IN_TIMESTAMP_LSB;
-const _LOOP_THRESHOLD: u64 = 10000;
-const _TIME_LOOP: u64 = _MAX_TIMESTAMP + (1 << NUM_BITS_IN_TIMESTAMP_LSB);
+const MAX_TIMESTAMP: u64 = ((1 << 28) - 1) << NUM_BITS_IN_TIMESTAMP_LSB;
+const LOOP_THRESHOLD: u64 = 10000;
+const TIME_LOOP: u64 = MAX_TIMESTAMP + (1 << NUM_BITS_IN_TIMESTAMP_LSB); // = 2^34us, about 4.77h
 
 const CD_OFF: u8 = 0b0000;
 const CD_ON: u8 = 0b0001;
@@ -33,9 +43,25 @@ const EXT_TRIGGER: u8 = 0b1010;
 const _OTHERS: u8 = 0b1110;
 const _CONTINUED: u8 = 0b1111;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Evt2Decoder {
     time_high: Option<u64>,
+    time_high_loop_nb: u64, // Counts overflows of time high
+    unknown_policy: UnknownPolicy,
+    unknown_words: u64, // Counts words of unknown event types, whatever the policy
+    decoded_words: bool, // Whether any word was given to the decoder
+    error: Option<DecodeError>,
+}
+
+impl Evt2Decoder {
+    /// Sets how words of unknown event types are handled
+    pub fn with_unknown_policy(self, unknown_policy: UnknownPolicy) -> Self {
+        Evt2Decoder {
+            unknown_policy,
+            ..self
+        }
+    }
 }
 
 impl EventDecoder for Evt2Decoder {
@@ -45,29 +71,168 @@ impl EventDecoder for Evt2Decoder {
         Self::default()
     }
 
+    fn format_name() -> &'static str {
+        "EVT2"
+    }
+
     fn decode(&mut self, raw_event: &[Self::RawEventType], event_queue: &mut VecDeque<Event>) {
+        self.decoded_words |= !raw_event.is_empty();
         raw_event.iter().for_each(|evt| {
+            if self.error.is_some() {
+                return;
+            }
             match evt.event_type() {
                 CD_ON | CD_OFF if self.time_high.is_some() => {
                     let full_timestamp = self.time_high.unwrap() | evt.time_low();
                     event_queue.push_back(Event::CD {
                         x: evt.x(),
                         y: evt.y(),
-                        p: evt.event_type(),
+                        p: (evt.event_type() == CD_ON) as u8,
                         t: full_timestamp,
                     })
                 }
                 EVT_TIME_HIGH => {
-                    self.time_high = Some(evt.time_high() << NUM_BITS_IN_TIMESTAMP_LSB)
+                    let mut new_time_high = (evt.time_high() << NUM_BITS_IN_TIMESTAMP_LSB)
+                        + self.time_high_loop_nb * TIME_LOOP;
+                    if let Some(time_high) = self.time_high {
+                        if time_high > new_time_high
+                            && time_high - new_time_high >= MAX_TIMESTAMP - LOOP_THRESHOLD
+                        {
+                            new_time_high += TIME_LOOP;
+                            self.time_high_loop_nb += 1;
+                        }
+                    }
+                    self.time_high = Some(new_time_high);
+                }
+                EXT_TRIGGER if self.time_high.is_some() => {
+                    event_queue.push_back(Event::ExternalTrigger {
+                        id: evt.trigger_channel_id(),
+                        p: evt.trigger_value(),
+                        t: self.time_high.unwrap() | evt.time_low(),
+                    })
+                }
+                CD_ON | CD_OFF | EXT_TRIGGER => {}
+                _ => {
+                    self.unknown_words += 1;
+                    match self.unknown_policy {
+                        UnknownPolicy::Emit => event_queue.push_back(Event::Unknown()),
+                        UnknownPolicy::Skip => {}
+                        UnknownPolicy::Error => {
+                            self.error = Some(DecodeError::UnknownEventType(evt.event_type()))
+                        }
+                    }
                 }
-                EXT_TRIGGER => event_queue.push_back(Event::ExternalTrigger {
-                    id: evt.trigger_channel_id(),
-                    p: evt.trigger_value(),
-                    t: 0,
-                }),
-                CD_ON | CD_OFF => {}
-                _ => event_queue.push_back(Event::Unknown()),
             }; // end match type of event
         });
     }
+
+    fn decode_error(&self) -> Option<DecodeError> {
+        self.error
+    }
+
+    fn unknown_word_count(&self) -> u64 {
+        self.unknown_words
+    }
+
+    fn time_base_missing(&self) -> bool {
+        self.decoded_words && self.time_high.is_none()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl crate::par::ChunkedDecoder for Evt2Decoder {
+    fn is_time_high(word: &Evt2) -> bool {
+        word.event_type() == EVT_TIME_HIGH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(decoder: &mut Evt2Decoder, words: &[u32]) -> Vec<Event> {
+        let words: Vec<Evt2> = words.iter().map(|&data| Evt2 { data }).collect();
+        let mut events = VecDeque::new();
+        decoder.decode(&words, &mut events);
+        events.into()
+    }
+
+    #[test]
+    fn test_unknown_policy() {
+        let cd = |x: u32| ((CD_ON as u32) << 28) | (x << 11);
+        let words = [
+            (EVT_TIME_HIGH as u32) << 28,
+            cd(1),
+            0xE << 28,
+            cd(2),
+            0xF << 28,
+            cd(3),
+        ];
+
+        let skipped = decode(&mut Evt2Decoder::new(), &words);
+        assert_eq!(skipped.len(), 3);
+        assert!(skipped.iter().all(Event::is_cd));
+
+        let emitted = decode(
+            &mut Evt2Decoder::new().with_unknown_policy(UnknownPolicy::Emit),
+            &words,
+        );
+        assert_eq!(emitted.len(), 5);
+        assert_eq!(emitted[1], Event::Unknown());
+
+        let mut decoder = Evt2Decoder::new().with_unknown_policy(UnknownPolicy::Error);
+        let events = decode(&mut decoder, &words);
+        assert_eq!(events, vec![Event::cd(1, 0, 1, 0)]);
+        assert_eq!(
+            decoder.decode_error(),
+            Some(DecodeError::UnknownEventType(0xE))
+        );
+        assert!(decode(&mut decoder, &words).is_empty());
+    }
+
+    #[test]
+    fn test_time_high_rollover() {
+        let time_high = |t: u32| ((EVT_TIME_HIGH as u32) << 28) | t;
+        let cd = |t_low: u32| ((CD_ON as u32) << 28) | (t_low << 22);
+        let words = [
+            time_high(0x0FFF_FFFE),
+            cd(10),
+            time_high(0x0FFF_FFFF),
+            cd(63),
+            time_high(0),
+            cd(0),
+            time_high(1),
+            cd(5),
+        ];
+
+        let events = decode(&mut Evt2Decoder::new(), &words);
+        let timestamps: Vec<u64> = events.iter().filter_map(Event::timestamp).collect();
+        assert_eq!(timestamps.len(), 4);
+        assert!(timestamps.windows(2).all(|t| t[0] < t[1]));
+        assert_eq!(timestamps[1], MAX_TIMESTAMP | 63);
+        assert_eq!(timestamps[2], TIME_LOOP);
+        assert_eq!(timestamps[3], TIME_LOOP + 64 + 5);
+    }
+
+    #[test]
+    fn test_timestamp_bits() {
+        // The time high payload holds bits 33..6 of the timestamp, and the
+        // time low field (bits 27..22 of the word) holds bits 5..0
+        let words = [
+            0x8000_0001, // Time high 1, 64us
+            0x1A8A_AAAA, // CD on, time low 42, x 341, y 682
+            0x80AB_CDEF, // Time high 0xABCDEF
+            0x0FC0_0000, // CD off, time low 63, x 0, y 0
+            0xA440_0301, // Trigger, time low 17, channel 3, value 1
+        ];
+        let events = decode(&mut Evt2Decoder::new(), &words);
+        assert_eq!(
+            events,
+            vec![
+                Event::cd(341, 682, 1, 106),
+                Event::cd(0, 0, 0, 0xABCDEF * 64 + 63),
+                Event::trigger(3, 1, 720_600_017),
+            ]
+        );
+    }
 }
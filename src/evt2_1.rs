@@ -1,4 +1,4 @@
-use crate::{declare_raw_evt, Event, EventDecoder};
+use crate::{declare_raw_evt, DecodeError, Event, EventDecoder, RawWord, UnknownPolicy};
 use std::collections::VecDeque;
 use zerocopy::{FromBytes, Immutable, KnownLayout};
 
@@ -20,15 +20,97 @@ declare_raw_evt! {
     trigger_value(u8): 32, 32;
 }
 
+impl RawWord for Evt21 {
+    fn raw(&self) -> u64 {
+        self.data
+    }
+
+    fn word_type(&self) -> u8 {
+        self.event_type()
+    }
+}
+
 const EVT_NEG: u8 = 0b0000;
 const EVT_POS: u8 = 0b0001;
 const EVT_TIME_HIGH: u8 = 0b1000;
 const EXT_TRIGGER: u8 = 0b1010;
 const _OTHERS: u8 = 0b1110;
 
-#[derive(Default)]
+const NUM_BITS_IN_TIMESTAMP_LSB: u64 = 6;
+const MAX_TIMESTAMP: u64 = ((1 << 28) - 1) << NUM_BITS_IN_TIMESTAMP_LSB;
+const LOOP_THRESHOLD: u64 = 10000;
+const TIME_LOOP: u64 = MAX_TIMESTAMP + (1 << NUM_BITS_IN_TIMESTAMP_LSB); // = 2^34us, about 4.77h
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Evt21Decoder {
     time_high: Option<u64>,
+    time_high_loop_nb: u64, // Counts overflows of time high
+    width: u16,             // Events with x >= width are dropped
+    dropped_events: u64,    // Counts events dropped for being out of the sensor
+    strict_width: bool,     // Whether a vector crossing the end of the row is an error
+    unknown_policy: UnknownPolicy,
+    unknown_words: u64, // Counts words of unknown event types, whatever the policy
+    decoded_words: bool, // Whether any word was given to the decoder
+    error: Option<DecodeError>,
+}
+
+impl Default for Evt21Decoder {
+    fn default() -> Self {
+        Evt21Decoder {
+            time_high: None,
+            time_high_loop_nb: 0,
+            width: u16::MAX,
+            dropped_events: 0,
+            strict_width: false,
+            unknown_policy: UnknownPolicy::default(),
+            unknown_words: 0,
+            decoded_words: false,
+            error: None,
+        }
+    }
+}
+
+impl Evt21Decoder {
+    /// Creates a decoder that drops the CD events with `x >= width`, which a
+    /// vector event close to the end of a row can produce on a corrupted
+    /// stream. A width of 0 means the geometry is unknown and disables the check.
+    pub fn with_width(width: u32) -> Self {
+        let width = match width {
+            0 => u16::MAX,
+            width => width.min(u16::MAX as u32) as u16,
+        };
+        Evt21Decoder {
+            width,
+            ..Self::default()
+        }
+    }
+
+    /// Makes a vector event crossing the end of the row a decoding error
+    /// instead of dropping its events out of the sensor.
+    ///
+    /// A vector event holds up to 32 events of a row, at `x` plus the index of
+    /// each bit set in its mask. The camera never sets a bit past the end of
+    /// the row, so such a vector means the file is corrupted.
+    pub fn with_strict_width(self, strict_width: bool) -> Self {
+        Evt21Decoder {
+            strict_width,
+            ..self
+        }
+    }
+
+    /// Number of CD events dropped because they were out of the sensor
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped_events
+    }
+
+    /// Sets how words of unknown event types are handled
+    pub fn with_unknown_policy(self, unknown_policy: UnknownPolicy) -> Self {
+        Evt21Decoder {
+            unknown_policy,
+            ..self
+        }
+    }
 }
 
 impl EventDecoder for Evt21Decoder {
@@ -38,26 +120,61 @@ impl EventDecoder for Evt21Decoder {
         Self::default()
     }
 
+    fn format_name() -> &'static str {
+        "EVT2.1"
+    }
+
     fn decode(&mut self, raw_event: &[Self::RawEventType], event_queue: &mut VecDeque<Event>) {
+        self.decoded_words |= !raw_event.is_empty();
         raw_event.iter().for_each(|evt| {
+            if self.error.is_some() {
+                return;
+            }
             match evt.event_type() {
                 EVT_NEG | EVT_POS if self.time_high.is_some() => {
                     // Compute the full timestamp
                     let full_timestamp = self.time_high.unwrap() | evt.timestamp();
                     let mut mask = evt.valid_mask();
+                    if self.strict_width && mask != 0 {
+                        let last_x = u32::from(evt.x()) + 31 - mask.leading_zeros();
+                        if last_x >= u32::from(self.width) {
+                            self.error = Some(DecodeError::VectorOutOfRow {
+                                x: evt.x(),
+                                y: evt.y(),
+                            });
+                            return;
+                        }
+                    }
                     while mask != 0 {
                         let offset = mask.trailing_zeros();
                         // Clear the lowest set bit
                         mask = mask & (mask - 1);
+                        let x = evt.x() + offset as u16;
+                        if x >= self.width {
+                            self.dropped_events += 1;
+                            continue;
+                        }
                         event_queue.push_back(Event::CD {
-                            x: evt.x() + offset as u16,
+                            x,
                             y: evt.y(),
-                            p: evt.event_type(), // Use the event type for the polarity because CD_OFF is 0x0 and CD_ON is 0x1
+                            p: (evt.event_type() == EVT_POS) as u8,
                             t: full_timestamp,
                         });
                     }
                 }
-                EVT_TIME_HIGH => self.time_high = Some(evt.time_high() << 6),
+                EVT_TIME_HIGH => {
+                    let mut new_time_high = (evt.time_high() << NUM_BITS_IN_TIMESTAMP_LSB)
+                        + self.time_high_loop_nb * TIME_LOOP;
+                    if let Some(time_high) = self.time_high {
+                        if time_high > new_time_high
+                            && time_high - new_time_high >= MAX_TIMESTAMP - LOOP_THRESHOLD
+                        {
+                            new_time_high += TIME_LOOP;
+                            self.time_high_loop_nb += 1;
+                        }
+                    }
+                    self.time_high = Some(new_time_high);
+                }
                 EXT_TRIGGER if self.time_high.is_some() => {
                     let full_timestamp = self.time_high.unwrap() | evt.timestamp();
                     event_queue.push_back(Event::ExternalTrigger {
@@ -68,8 +185,108 @@ impl EventDecoder for Evt21Decoder {
                 }
                 EVT_NEG | EVT_POS => {}
                 EXT_TRIGGER => {}
-                _ => event_queue.push_back(Event::Unknown()),
+                _ => {
+                    self.unknown_words += 1;
+                    match self.unknown_policy {
+                        UnknownPolicy::Emit => event_queue.push_back(Event::Unknown()),
+                        UnknownPolicy::Skip => {}
+                        UnknownPolicy::Error => {
+                            self.error = Some(DecodeError::UnknownEventType(evt.event_type()))
+                        }
+                    }
+                }
             }
         });
     }
+
+    fn decode_error(&self) -> Option<DecodeError> {
+        self.error
+    }
+
+    fn unknown_word_count(&self) -> u64 {
+        self.unknown_words
+    }
+
+    fn time_base_missing(&self) -> bool {
+        self.decoded_words && self.time_high.is_none()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl crate::par::ChunkedDecoder for Evt21Decoder {
+    fn is_time_high(word: &Evt21) -> bool {
+        word.event_type() == EVT_TIME_HIGH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_clamped_to_width() {
+        let mut decoder = Evt21Decoder::with_width(320);
+        let mut events = VecDeque::new();
+        let words = [
+            Evt21 {
+                data: (EVT_TIME_HIGH as u64) << 60 | 1 << 32,
+            },
+            Evt21 {
+                data: (EVT_POS as u64) << 60 | 300 << 43 | 10 << 32 | 0xFFFF_FFFF,
+            },
+        ];
+        decoder.decode(&words, &mut events);
+
+        assert_eq!(events.len(), 20);
+        assert!(events.iter().all(|e| e.x().unwrap() < 320));
+        assert_eq!(decoder.dropped_events(), 12);
+    }
+
+    #[test]
+    fn test_strict_width() {
+        let time_high = Evt21 {
+            data: (EVT_TIME_HIGH as u64) << 60 | 1 << 32,
+        };
+        let vector = |x: u64, mask: u64| Evt21 {
+            data: (EVT_POS as u64) << 60 | x << 43 | 10 << 32 | mask,
+        };
+
+        // The last bit set lands on the last pixel of the row
+        let mut decoder = Evt21Decoder::with_width(320).with_strict_width(true);
+        let mut events = VecDeque::new();
+        decoder.decode(&[time_high, vector(300, 0x0008_0001)], &mut events);
+        assert_eq!(events.len(), 2);
+        assert_eq!(decoder.decode_error(), None);
+
+        decoder.decode(&[vector(300, 0x0010_0001), vector(0, 1)], &mut events);
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            decoder.decode_error(),
+            Some(DecodeError::VectorOutOfRow { x: 300, y: 10 })
+        );
+    }
+
+    #[test]
+    fn test_time_high_rollover() {
+        let mut decoder = Evt21Decoder::new();
+        let mut events = VecDeque::new();
+        let time_high = |t: u64| Evt21 {
+            data: (EVT_TIME_HIGH as u64) << 60 | t << 32,
+        };
+        let cd = Evt21 {
+            data: (EVT_POS as u64) << 60 | 1,
+        };
+        let words = [
+            time_high(0x0FFF_FFFF),
+            cd,
+            time_high(0),
+            cd,
+            time_high(1),
+            cd,
+        ];
+        decoder.decode(&words, &mut events);
+
+        let timestamps: Vec<u64> = events.iter().filter_map(Event::timestamp).collect();
+        assert_eq!(timestamps, vec![MAX_TIMESTAMP, TIME_LOOP, TIME_LOOP + 64]);
+    }
 }
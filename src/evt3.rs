@@ -1,4 +1,4 @@
-use crate::{declare_raw_evt, Event, EventDecoder};
+use crate::{declare_raw_evt, DecodeError, Event, EventDecoder, RawWord, UnknownPolicy};
 use zerocopy::{FromBytes, Immutable, KnownLayout};
 
 // Struct for holding raw EVT3 types
@@ -15,6 +15,16 @@ declare_raw_evt! {
     trigger_polarity(u8): 0, 0;
 }
 
+impl RawWord for Evt3 {
+    fn raw(&self) -> u64 {
+        u64::from(self.data)
+    }
+
+    fn word_type(&self) -> u8 {
+        self.event_type()
+    }
+}
+
 const EVT_ADDR_Y: u8 = 0x0;
 const EVT_ADDR_X: u8 = 0x2;
 const VECT_BASE_X: u8 = 0x3;
@@ -24,19 +34,27 @@ const EVT_TIME_LOW: u8 = 0x6;
 const EVT_TIME_HIGH: u8 = 0x8;
 const EXT_TRIGGER: u8 = 0xA;
 
+// Emits the events of a VECT_12 or VECT_8 word. Vectors continue from the
+// x reached by the previous one, starting at the x and with the polarity of
+// the last VECT_BASE_X. The x saturates instead of wrapping around on a
+// corrupted stream with too many vectors in a row.
 macro_rules! handle_vect {
     ($state:expr, $events:expr, $valid:expr, $vect_size:expr) => {{
-        let end = $state.x + $vect_size;
+        let end = $state.x.saturating_add($vect_size);
         let mut valid_bits = $valid;
 
         for i in $state.x..end {
             if valid_bits & 1 == 1 {
-                $events.push_back(Event::CD {
-                    x: i,
-                    y: $state.y,
-                    p: $state.polarity,
-                    t: $state.time,
-                });
+                if i < $state.width {
+                    $events.push_back(Event::CD {
+                        x: i,
+                        y: $state.y,
+                        p: $state.polarity,
+                        t: $state.time,
+                    });
+                } else {
+                    $state.dropped_events += 1;
+                }
             }
             valid_bits >>= 1;
         }
@@ -48,6 +66,8 @@ const MAX_TIMESTAMP_BASE: u64 = ((1u64 << 12) - 1) << 12; // = 16773120us
 const TIME_LOOP_DURATION_US: u64 = MAX_TIMESTAMP_BASE + (1 << 12); // = 16777216us
 const LOOP_THRESHOLD: u64 = 10 << 12; // It could be another value too, as long as it is a big enough value that we can be sure that the time high looped
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Evt3Decoder {
     time: u64,
     time_base: Option<u64>, // Keeps track of time high (base time)
@@ -55,6 +75,41 @@ pub struct Evt3Decoder {
     polarity: u8,
     x: u16,
     y: u16,
+    width: u16,          // Events with x >= width are dropped
+    dropped_events: u64, // Counts events dropped for being out of the sensor
+    unknown_policy: UnknownPolicy,
+    unknown_words: u64, // Counts words of unknown event types, whatever the policy
+    decoded_words: bool, // Whether any word was given to the decoder
+    error: Option<DecodeError>,
+}
+
+impl Evt3Decoder {
+    /// Creates a decoder that drops the CD events with `x >= width`, as
+    /// produced by malformed or truncated streams. A width of 0 means the
+    /// geometry is unknown and disables the check.
+    pub fn with_width(width: u32) -> Self {
+        let width = match width {
+            0 => u16::MAX,
+            width => width.min(u16::MAX as u32) as u16,
+        };
+        Evt3Decoder {
+            width,
+            ..Self::new()
+        }
+    }
+
+    /// Number of CD events dropped because they were out of the sensor
+    pub fn dropped_events(&self) -> u64 {
+        self.dropped_events
+    }
+
+    /// Sets how words of unknown event types are handled
+    pub fn with_unknown_policy(self, unknown_policy: UnknownPolicy) -> Self {
+        Evt3Decoder {
+            unknown_policy,
+            ..self
+        }
+    }
 }
 
 impl EventDecoder for Evt3Decoder {
@@ -68,15 +123,29 @@ impl EventDecoder for Evt3Decoder {
             polarity: 0,
             x: 0,
             y: 0,
+            width: u16::MAX,
+            dropped_events: 0,
+            unknown_policy: UnknownPolicy::default(),
+            unknown_words: 0,
+            decoded_words: false,
+            error: None,
         }
     }
 
+    fn format_name() -> &'static str {
+        "EVT3"
+    }
+
     fn decode(
         &mut self,
         raw_event: &[Self::RawEventType],
         event_queue: &mut std::collections::VecDeque<Event>,
     ) {
+        self.decoded_words |= !raw_event.is_empty();
         raw_event.iter().for_each(|evt| {
+            if self.error.is_some() {
+                return;
+            }
             // Process the event based on its type
             match evt.event_type() {
                 EVT_ADDR_Y => {
@@ -86,6 +155,10 @@ impl EventDecoder for Evt3Decoder {
                     if self.time_base.is_none() {
                         return;
                     }
+                    if evt.x() >= self.width {
+                        self.dropped_events += 1;
+                        return;
+                    }
                     // Create Event
                     event_queue.push_back(Event::CD {
                         x: evt.x(),
@@ -149,6 +222,9 @@ impl EventDecoder for Evt3Decoder {
                     self.time = time_base;
                 }
                 EXT_TRIGGER => {
+                    if self.time_base.is_none() {
+                        return;
+                    }
                     event_queue.push_back(Event::ExternalTrigger {
                         id: evt.trigger_id(),
                         p: evt.trigger_polarity(),
@@ -156,9 +232,286 @@ impl EventDecoder for Evt3Decoder {
                     });
                 }
                 _ => {
-                    event_queue.push_back(Event::Unknown());
+                    self.unknown_words += 1;
+                    match self.unknown_policy {
+                        UnknownPolicy::Emit => event_queue.push_back(Event::Unknown()),
+                        UnknownPolicy::Skip => {}
+                        UnknownPolicy::Error => {
+                            self.error = Some(DecodeError::UnknownEventType(evt.event_type()))
+                        }
+                    }
                 }
             }
         });
     }
+
+    fn decode_error(&self) -> Option<DecodeError> {
+        self.error
+    }
+
+    fn unknown_word_count(&self) -> u64 {
+        self.unknown_words
+    }
+
+    fn time_base_missing(&self) -> bool {
+        self.decoded_words && self.time_base.is_none()
+    }
+}
+
+// Number of words scanned after a time high to decide if a chunk can start there
+#[cfg(feature = "rayon")]
+const SAFE_BOUNDARY_SCAN_WORDS: usize = 256;
+
+#[cfg(feature = "rayon")]
+impl crate::par::ChunkedDecoder for Evt3Decoder {
+    fn is_time_high(word: &Evt3) -> bool {
+        word.event_type() == EVT_TIME_HIGH
+    }
+
+    /// The row (`EVT_ADDR_Y`) and the vector base (`VECT_BASE_X`) are carried
+    /// across words, so a chunk can only start on a time high that is followed
+    /// by a new row before any address event, and by a new vector base before
+    /// any vector event.
+    fn is_safe_boundary(words: &[Evt3], index: usize) -> bool {
+        if !Self::is_time_high(&words[index]) {
+            return false;
+        }
+        let mut has_y = false;
+        let mut has_vect_base = false;
+        for word in words[index + 1..].iter().take(SAFE_BOUNDARY_SCAN_WORDS) {
+            match word.event_type() {
+                EVT_ADDR_Y => has_y = true,
+                VECT_BASE_X => has_vect_base = true,
+                EVT_ADDR_X if !has_y => return false,
+                VECT_12 | VECT_8 if !has_y || !has_vect_base => return false,
+                _ => {}
+            }
+            if has_y && has_vect_base {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Encodes events into EVT3 raw words, the inverse of [`Evt3Decoder`].
+///
+/// Each CD event is written as a single address event, preceded by the time
+/// and row words when they change. The events must be sorted by timestamp.
+/// As the decoder can't know how many 16.78s time loops happened before the
+/// stream starts, a stream whose first event is past the first loop starts
+/// with a time high of 0 followed by the loops crossed. Unknown events are
+/// skipped.
+#[derive(Debug, Default, Clone)]
+pub struct Evt3Encoder {
+    time_high: Option<u64>, // Time high of the last word, including the time loops
+    time_low: Option<u64>,
+    y: Option<u16>,
+}
+
+impl Evt3Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the words of `event` to `out`, as little endian bytes
+    pub fn encode(&mut self, event: &Event, out: &mut Vec<u8>) {
+        let push = |out: &mut Vec<u8>, event_type: u8, payload: u16| {
+            out.extend(((event_type as u16) << 12 | payload & 0xFFF).to_le_bytes())
+        };
+        let Some(t) = event.timestamp() else {
+            return;
+        };
+
+        let time_high = t >> 12;
+        if self.time_high != Some(time_high) {
+            let mut current = self.time_high;
+            if current.is_none() && time_high >> 12 > 0 {
+                push(out, EVT_TIME_HIGH, 0);
+                current = Some(0);
+            }
+            if let Some(mut current) = current {
+                // The decoder detects a time loop when the time high goes
+                // back, so every loop crossed must be written
+                while current >> 12 < time_high >> 12 {
+                    if current & 0xFFF != 0xFFF {
+                        push(out, EVT_TIME_HIGH, 0xFFF);
+                    }
+                    push(out, EVT_TIME_HIGH, 0);
+                    current = ((current >> 12) + 1) << 12;
+                }
+            }
+            push(out, EVT_TIME_HIGH, (time_high & 0xFFF) as u16);
+            self.time_high = Some(time_high);
+            self.time_low = None;
+        }
+        if self.time_low != Some(t & 0xFFF) {
+            push(out, EVT_TIME_LOW, (t & 0xFFF) as u16);
+            self.time_low = Some(t & 0xFFF);
+        }
+
+        match *event {
+            Event::CD { x, y, p, .. } => {
+                if self.y != Some(y) {
+                    push(out, EVT_ADDR_Y, y & 0x7FF);
+                    self.y = Some(y);
+                }
+                push(out, EVT_ADDR_X, (p as u16 & 1) << 11 | x & 0x7FF);
+            }
+            Event::ExternalTrigger { id, p, .. } => {
+                push(out, EXT_TRIGGER, (id as u16 & 0xF) << 8 | p as u16 & 1);
+            }
+            Event::Unknown() => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    fn word(event_type: u8, payload: u16) -> Evt3 {
+        Evt3 {
+            data: (event_type as u16) << 12 | payload,
+        }
+    }
+
+    #[test]
+    fn test_vector_clamped_to_width() {
+        let mut decoder = Evt3Decoder::with_width(640);
+        let mut events = VecDeque::new();
+        let words = [
+            word(EVT_TIME_HIGH, 1),
+            word(EVT_ADDR_Y, 12),
+            word(VECT_BASE_X, 1 << 11 | 630),
+            word(VECT_12, 0xFFF),
+            word(EVT_ADDR_X, 1 << 11 | 700),
+        ];
+        decoder.decode(&words, &mut events);
+
+        assert_eq!(events.len(), 10);
+        assert!(events.iter().all(|e| e.x().unwrap() < 640));
+        assert_eq!(decoder.dropped_events(), 3);
+
+        let mut decoder = Evt3Decoder::with_width(0);
+        let mut events = VecDeque::new();
+        decoder.decode(&words, &mut events);
+        assert_eq!(events.len(), 13);
+    }
+
+    #[test]
+    fn test_vector_continuation() {
+        let xs = |events: &VecDeque<Event>, p: u8| -> Vec<u16> {
+            assert!(events.iter().all(|e| e.polarity() == Some(p)));
+            events.iter().filter_map(Event::x).collect()
+        };
+        let mut decoder = Evt3Decoder::new();
+        let mut events = VecDeque::new();
+        decoder.decode(
+            &[
+                word(EVT_TIME_HIGH, 1),
+                word(EVT_ADDR_Y, 5),
+                word(VECT_BASE_X, 1 << 11 | 10),
+                word(VECT_12, 0xFFF),
+                word(VECT_8, 0xFF),
+            ],
+            &mut events,
+        );
+        assert_eq!(xs(&events, 1), (10..30).collect::<Vec<u16>>());
+
+        // A new base resets the x and the polarity after a partial vector
+        events.clear();
+        decoder.decode(
+            &[
+                word(VECT_BASE_X, 1 << 11 | 100),
+                word(VECT_12, 0b1),
+                word(VECT_BASE_X, 40),
+                word(VECT_8, 0b101),
+            ],
+            &mut events,
+        );
+        assert_eq!(events[0], Event::cd(100, 5, 1, 4096));
+        events.pop_front();
+        assert_eq!(xs(&events, 0), vec![40, 42]);
+
+        // Vectors without a new base never wrap the x around
+        events.clear();
+        decoder.decode(&[word(VECT_12, 0); 6000], &mut events);
+        decoder.decode(&[word(VECT_8, 0xFF)], &mut events);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn test_trigger_before_time_high() {
+        let mut decoder = Evt3Decoder::new();
+        let mut events = VecDeque::new();
+        decoder.decode(
+            &[
+                word(EXT_TRIGGER, 2 << 8 | 1),
+                word(EVT_TIME_HIGH, 1),
+                word(EXT_TRIGGER, 2 << 8),
+            ],
+            &mut events,
+        );
+        assert_eq!(events, [Event::trigger(2, 0, 4096)]);
+        assert_eq!(decoder.unknown_word_count(), 0);
+    }
+
+    #[test]
+    fn test_time_rollover() {
+        let header = crate::RawFileHeader {
+            header_dict: Default::default(),
+            event_type: crate::RawEventType::Evt3,
+            camera_geometry: crate::CameraGeometry {
+                width: 0,
+                height: 0,
+            },
+            endianness: crate::Endianness::Little,
+            endianness_declared: false,
+            header_len: 0,
+        };
+        assert_eq!(header.timestamp_bits(), 24);
+        assert_eq!(header.time_rollover_us(), TIME_LOOP_DURATION_US);
+    }
+
+    #[test]
+    fn test_encoder_round_trip() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(0x3e7);
+        for _ in 0..200 {
+            let mut t = rng.random_range(0..3 * TIME_LOOP_DURATION_US);
+            let events: Vec<Event> = (0..rng.random_range(0..2000))
+                .map(|_| {
+                    let event = match rng.random_range(0..20) {
+                        0 => Event::trigger(rng.random_range(0..16), rng.random_range(0..2), t),
+                        _ => Event::cd(
+                            rng.random_range(0..1280),
+                            rng.random_range(0..720),
+                            rng.random_range(0..2),
+                            t,
+                        ),
+                    };
+                    // Mostly small steps, with a few gaps spanning time loops
+                    t += match rng.random_range(0..100) {
+                        0 => rng.random_range(0..3 * TIME_LOOP_DURATION_US),
+                        1..10 => rng.random_range(0..100_000),
+                        _ => rng.random_range(0..50),
+                    };
+                    event
+                })
+                .collect();
+
+            let mut encoder = Evt3Encoder::new();
+            let mut bytes = Vec::new();
+            events
+                .iter()
+                .for_each(|event| encoder.encode(event, &mut bytes));
+
+            let decoded: Vec<Event> =
+                crate::EvtReader::new(std::io::Cursor::new(bytes), Evt3Decoder::new()).collect();
+            assert_eq!(decoded, events);
+        }
+    }
 }
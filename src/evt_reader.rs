@@ -1,42 +1,434 @@
-use crate::{Event, EventDecoder};
+use crate::{DecodeError, Endianness, Event, EventDecoder, RawWord};
 use std::{collections::VecDeque, io::Read};
-use zerocopy::FromBytes;
-const READ_BUFFER_SIZE: usize = 512;
-const _: () = {
-    assert!(READ_BUFFER_SIZE % 8 == 0);
-};
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout};
 
-#[repr(align(64))]
-struct AlignedBuffer([u8; READ_BUFFER_SIZE]);
+/// Default size of the read buffer in bytes
+pub const DEFAULT_READ_BUFFER_SIZE: usize = 512;
+
+// Most events a single raw word can produce, reached by EVT2.1 vectors
+const MAX_EVENTS_PER_WORD: usize = 32;
+
+// Number of words decoded at once from a memory map
+#[cfg(feature = "mmap")]
+const MMAP_CHUNK_WORDS: usize = 1 << 16;
+
+// The read buffer is stored as 64-byte aligned chunks so that the raw words
+// can always be reinterpreted in place whatever the buffer size is
+#[derive(FromBytes, IntoBytes, Immutable, KnownLayout, Copy, Clone)]
+#[repr(C, align(64))]
+struct AlignedChunk([u8; 64]);
+
+/// Reverses the bytes of each word of `bytes`, to decode words that aren't in
+/// the native byte order
+pub(crate) fn swap_word_bytes(bytes: &mut [u8], word_size: usize) {
+    bytes.chunks_exact_mut(word_size).for_each(<[u8]>::reverse);
+}
+
+// Event data of a memory mapped file, decoded in place
+#[cfg(feature = "mmap")]
+struct MappedWords {
+    mmap: memmap2::Mmap,
+    position: usize, // Offset of the next word to decode
+    // Copy of the words that can't be reinterpreted in place, because they
+    // are misaligned or must be swapped
+    aligned: Vec<AlignedChunk>,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedWords {
+    /// Returns the next `count` words at most, advancing the position. The
+    /// words are swapped with [`swap_word_bytes`] when `swap_bytes` is set.
+    fn next_words<W>(&mut self, count: usize, swap_bytes: bool) -> &[W]
+    where
+        W: FromBytes + Immutable + KnownLayout,
+    {
+        let word_size = std::mem::size_of::<W>();
+        // The position is past the end of a file truncated in its header
+        let count = count.min(self.mmap.len().saturating_sub(self.position) / word_size);
+        let start = self.position.min(self.mmap.len());
+        let bytes = &self.mmap[start..start + count * word_size];
+        self.position += bytes.len();
+
+        if !swap_bytes {
+            if let Ok(words) = <[W]>::ref_from_bytes_with_elems(bytes, count) {
+                return words;
+            }
+        }
+        self.aligned
+            .resize(bytes.len().div_ceil(64), AlignedChunk([0; 64]));
+        let copy = &mut self.aligned.as_mut_bytes()[..bytes.len()];
+        copy.copy_from_slice(bytes);
+        if swap_bytes {
+            swap_word_bytes(copy, word_size);
+        }
+        <[W]>::ref_from_bytes_with_elems(copy, count).unwrap()
+    }
+}
+
+/// Snapshot of an [`EvtReader`] to resume the decoding later, possibly in
+/// another process, see [`EvtReader::save_state`].
+///
+/// The decoder carries its configuration along with its position in the
+/// stream, like the sensor width, the [`crate::UnknownPolicy`] and the strict
+/// width of EVT2.1, which replace the ones of the restored reader. The
+/// options of the reader itself, like its byte order, CD filter or polarity
+/// inversion, aren't saved: the pending events were already converted with
+/// the options of the saved reader.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecoderState<D> {
+    /// Offset in the stream of the first word not decoded yet
+    pub stream_offset: u64,
+    /// Decoder at `stream_offset`, with its time base, vector position and
+    /// configuration
+    pub decoder: D,
+    /// Events decoded before `stream_offset` that weren't yielded yet
+    pub pending_events: Vec<Event>,
+}
 
 pub struct EvtReader<R: Read, D: EventDecoder> {
     reader: R,
     decoder: D,
-    buffer: AlignedBuffer,
+    buffer: Vec<AlignedChunk>,
+    buffer_size: usize,
     event_queue: VecDeque<Event>,
     read_buffer_cursor: usize,
+    decoded_bytes: usize, // Bytes of the read buffer already given to the decoder
+    filled_bytes: usize,  // Bytes of whole words in the read buffer
+    stream_offset: u64,   // Bytes of the stream given to the decoder
+    max_queue_size: usize,
+    cd_only: bool,
+    invert_polarity: bool,
+    swap_bytes: bool, // Whether the words aren't in the native byte order
+    max_events: Option<usize>,
+    max_duration_us: Option<u64>,
+    limited_events: usize,        // Events counted against max_events
+    first_timestamp: Option<u64>, // Start of the max_duration_us window
+    limit_reached: bool,
+    #[cfg(feature = "mmap")]
+    mapped: Option<MappedWords>,
 }
 
 impl<R: Read, D: EventDecoder> EvtReader<R, D> {
     pub fn new(reader: R, decoder: D) -> Self {
+        Self::with_capacity(reader, decoder, DEFAULT_READ_BUFFER_SIZE)
+    }
+
+    /// Creates a reader with a read buffer of `buffer_bytes` bytes.
+    ///
+    /// The size is rounded down to a multiple of the raw word size, with a
+    /// minimum of one word.
+    pub fn with_capacity(reader: R, decoder: D, buffer_bytes: usize) -> Self {
+        let word_size = D::word_size();
+        let buffer_size = (buffer_bytes - buffer_bytes % word_size).max(word_size);
+        let chunk_count = buffer_size.div_ceil(std::mem::size_of::<AlignedChunk>());
         EvtReader {
             reader,
             decoder,
-            buffer: AlignedBuffer([0; READ_BUFFER_SIZE]),
+            buffer: vec![AlignedChunk([0; 64]); chunk_count],
+            buffer_size,
             event_queue: VecDeque::<Event>::new(),
             read_buffer_cursor: 0,
+            decoded_bytes: 0,
+            filled_bytes: 0,
+            stream_offset: 0,
+            max_queue_size: usize::MAX,
+            cd_only: false,
+            invert_polarity: false,
+            swap_bytes: false,
+            max_events: None,
+            max_duration_us: None,
+            limited_events: 0,
+            first_timestamp: None,
+            limit_reached: false,
+            #[cfg(feature = "mmap")]
+            mapped: None,
         }
     }
-}
 
-impl<R: Read, D: EventDecoder> Iterator for EvtReader<R, D> {
-    type Item = Event;
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Drops every event that isn't a CD event when `cd_only` is set
+    pub fn with_cd_only(mut self, cd_only: bool) -> Self {
+        self.cd_only = cd_only;
+        self
+    }
+
+    /// Inverts the polarity of the CD events when `invert_polarity` is set
+    pub fn with_inverted_polarity(mut self, invert_polarity: bool) -> Self {
+        self.invert_polarity = invert_polarity;
+        self
+    }
+
+    /// Sets the byte order of the raw words, the words are swapped before
+    /// being decoded when it isn't the native one. Little endian by default.
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.swap_bytes = !endianness.is_native();
+        self
+    }
+
+    /// Stops decoding the read buffer once `max_queue_size` events are waiting
+    /// to be consumed, the rest of the buffer is decoded as the queue drains.
+    ///
+    /// This is a soft cap, the queue can exceed it by the events of a single
+    /// raw word. The queue is unbounded by default.
+    pub fn with_max_queue_size(mut self, max_queue_size: usize) -> Self {
+        self.max_queue_size = max_queue_size.max(1);
+        self
+    }
+
+    /// Stops the stream after `max_events` events, or at the first event
+    /// `max_duration_us` microseconds or more after the first timestamped
+    /// event, whichever comes first. Both are unbounded by default.
+    pub fn with_limits(mut self, max_events: Option<usize>, max_duration_us: Option<u64>) -> Self {
+        self.max_events = max_events;
+        self.max_duration_us = max_duration_us;
+        self
+    }
+
+    /// Mutable access to the underlying reader
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Mutable access to the decoder, to restore a copy saved at a checkpoint
+    pub fn decoder_mut(&mut self) -> &mut D {
+        &mut self.decoder
+    }
+
+    /// Number of bytes of the stream decoded so far. A reader of the stream
+    /// positioned at this offset, with a copy of the decoder, resumes the
+    /// decoding once the queued events are consumed.
+    pub fn stream_offset(&self) -> u64 {
+        self.stream_offset
+    }
+
+    /// Captures the decoding state, to resume it with
+    /// [`EvtReader::restore_state`]. The limits set by
+    /// [`EvtReader::with_limits`] aren't part of the state.
+    pub fn save_state(&self) -> DecoderState<D>
+    where
+        D: Clone,
+    {
+        DecoderState {
+            stream_offset: self.stream_offset,
+            decoder: self.decoder.clone(),
+            pending_events: self.event_queue.iter().copied().collect(),
+        }
+    }
+
+    /// Resumes the decoding from `state`, which this reader must not have
+    /// started: its stream must be positioned at `state.stream_offset` in
+    /// the event data. The pending events of the state are yielded first, and
+    /// the decoder of the state replaces the one of this reader.
+    pub fn restore_state(&mut self, state: DecoderState<D>) {
+        self.decoder = state.decoder;
+        self.event_queue = state.pending_events.into();
+        self.stream_offset = state.stream_offset;
+    }
+
+    /// Size of the read buffer in bytes
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Error that stopped the decoding, if any
+    pub fn decode_error(&self) -> Option<DecodeError> {
+        self.decoder.decode_error()
+    }
+
+    /// Number of words of unknown event types decoded so far, to assess the
+    /// integrity of a stream. They are counted whatever the
+    /// [`crate::UnknownPolicy`] of the decoder is.
+    pub fn decode_error_count(&self) -> u64 {
+        self.decoder.unknown_word_count()
+    }
+
+    /// Whether raw words were decoded without finding the time base of the
+    /// stream, like with a stream cut before its first time high word
+    pub fn time_base_missing(&self) -> bool {
+        self.decoder.time_base_missing()
+    }
+
+    /// Iterates over the raw words of the stream as `(raw, event_type)`
+    /// without running the decoder, for protocol debugging.
+    ///
+    /// The words are read from the same stream as the events: they start
+    /// after the last buffer decoded and are not decoded afterwards.
+    pub fn raw_words(&mut self) -> impl Iterator<Item = (u64, u8)> + '_
+    where
+        D::RawEventType: RawWord,
+    {
+        let mut bytes = vec![0; D::word_size()];
+        std::iter::from_fn(move || {
+            #[cfg(feature = "mmap")]
+            if let Some(mapped) = &mut self.mapped {
+                let word = *mapped
+                    .next_words::<D::RawEventType>(1, self.swap_bytes)
+                    .first()?;
+                return Some((word.raw(), word.word_type()));
+            }
+            self.reader.read_exact(&mut bytes).ok()?;
+            if self.swap_bytes {
+                swap_word_bytes(&mut bytes, D::word_size());
+            }
+            let word = D::RawEventType::read_from_bytes(&bytes).ok()?;
+            Some((word.raw(), word.word_type()))
+        })
+    }
+
+    /// Decodes up to `max` events and appends them to `out`.
+    ///
+    /// At least one read buffer is decoded when no events are pending, so this
+    /// amortizes the per-event iterator overhead. Returns the number of events
+    /// added to `out`, which is 0 only at the end of the stream.
+    pub fn next_batch(&mut self, out: &mut Vec<Event>, max: usize) -> usize {
+        if max == 0 {
+            return 0;
+        }
+        while self.event_queue.is_empty() {
+            if !self.fill_event_queue() {
+                return 0;
+            }
+        }
+        let count = max.min(self.event_queue.len());
+        out.extend(self.event_queue.drain(..count));
+        count
+    }
+
+    /// Calls `f` on every remaining event of the stream.
+    ///
+    /// The events are drained from the queue one decoded buffer at a time,
+    /// which avoids the per-event overhead of [`Iterator::next`].
+    pub fn for_each_event<F: FnMut(Event)>(&mut self, mut f: F) {
         loop {
-            if !self.event_queue.is_empty() {
-                return self.event_queue.pop_front();
+            self.event_queue.drain(..).for_each(&mut f);
+            if !self.fill_event_queue() {
+                return;
+            }
+        }
+    }
+
+    /// Drops the events before the first CD or trigger event at or after
+    /// `timestamp_us`, which is left to be yielded next
+    pub fn skip_before(&mut self, timestamp_us: u64) {
+        loop {
+            while let Some(event) = self.event_queue.front() {
+                if event.timestamp().is_some_and(|t| t >= timestamp_us) {
+                    return;
+                }
+                self.event_queue.pop_front();
+            }
+            if !self.fill_event_queue() {
+                return;
             }
+        }
+    }
 
+    /// Decodes the rest of the stream and returns the points it can be
+    /// resumed from, as `(last_us, offset, decoder)`: `last_us` is the latest
+    /// CD or trigger timestamp decoded before the stream offset `offset`, and
+    /// `decoder` the state of the decoder there.
+    ///
+    /// The checkpoints are at least `spacing` bytes apart, with none before
+    /// the first timestamped event.
+    pub(crate) fn checkpoints(&mut self, spacing: u64) -> Vec<(u64, u64, D)>
+    where
+        D: Clone,
+    {
+        let mut checkpoints = Vec::new();
+        let mut last_us = None;
+        let mut next_offset = self.stream_offset + spacing;
+        loop {
+            for event in self.event_queue.drain(..) {
+                if let Some(t) = event.timestamp() {
+                    last_us = Some(last_us.map_or(t, |last_us: u64| last_us.max(t)));
+                }
+            }
+            // Every event decoded so far is drained and counted in `last_us`,
+            // nothing is lost by resuming from here
+            if let Some(last_us) = last_us {
+                if self.stream_offset >= next_offset {
+                    checkpoints.push((last_us, self.stream_offset, self.decoder.clone()));
+                    next_offset = self.stream_offset + spacing;
+                }
+            }
+            if !self.fill_event_queue() {
+                return checkpoints;
+            }
+        }
+    }
+
+    /// Decodes the next raw events into the event queue, reading a new
+    /// buffer when the current one is fully decoded.
+    /// Returns false when the end of the stream, or a limit, is reached.
+    fn fill_event_queue(&mut self) -> bool {
+        if self.limit_reached || self.decoder.decode_error().is_some() {
+            return false;
+        }
+        let queued = self.event_queue.len();
+        if !self.decode_next_words() {
+            return false;
+        }
+        self.enforce_limits(queued);
+        true
+    }
+
+    /// Drops the events queued after `queued` that go past the max events or
+    /// the max duration, if any
+    fn enforce_limits(&mut self, queued: usize) {
+        if self.max_events.is_none() && self.max_duration_us.is_none() {
+            return;
+        }
+        for index in queued..self.event_queue.len() {
+            let over_duration = match (self.event_queue[index].timestamp(), self.max_duration_us) {
+                (Some(t), Some(max_duration_us)) => {
+                    t >= *self.first_timestamp.get_or_insert(t) + max_duration_us
+                }
+                _ => false,
+            };
+            let over_count = self
+                .max_events
+                .is_some_and(|max| self.limited_events >= max);
+            if over_duration || over_count {
+                self.event_queue.truncate(index);
+                self.limit_reached = true;
+                return;
+            }
+            self.limited_events += 1;
+        }
+    }
+
+    /// Decodes the next raw words into the event queue.
+    /// Returns false when the end of the stream is reached.
+    fn decode_next_words(&mut self) -> bool {
+        #[cfg(feature = "mmap")]
+        let count = self.words_to_decode(MMAP_CHUNK_WORDS);
+        #[cfg(feature = "mmap")]
+        if let Some(mapped) = &mut self.mapped {
+            let words = mapped.next_words(count, self.swap_bytes);
+            if words.is_empty() {
+                return false;
+            }
+            self.stream_offset += std::mem::size_of_val(words) as u64;
+            Self::decode_words(
+                &mut self.decoder,
+                words,
+                &mut self.event_queue,
+                self.cd_only,
+                self.invert_polarity,
+            );
+            return true;
+        }
+        if self.decoded_bytes == self.filled_bytes && !self.read_buffer() {
+            return false;
+        }
+        self.decode_pending_words();
+        true
+    }
+
+    /// Reads one buffer of raw events.
+    /// Returns false when the end of the stream is reached.
+    fn read_buffer(&mut self) -> bool {
+        loop {
             // If the timebase isn't set we need to find  it
             // if self.time_high.is_none() {
             //     let mut buffer: [u8; 8] = [0; 8];
@@ -49,40 +441,220 @@ impl<R: Read, D: EventDecoder> Iterator for EvtReader<R, D> {
             // }
 
             // Try to fill the read buffer
-            let bytes_read = self
-                .reader
-                .read(&mut self.buffer.0[self.read_buffer_cursor..])
-                .ok()?;
+            let buffer = &mut self.buffer.as_mut_bytes()[..self.buffer_size];
+            let Ok(bytes_read) = self.reader.read(&mut buffer[self.read_buffer_cursor..]) else {
+                return false;
+            };
 
             // Stop iteration when reaching end of stream
             if bytes_read == 0 && self.read_buffer_cursor == 0 {
-                return None;
+                return false;
             }
 
             // Update current cursor
             self.read_buffer_cursor += bytes_read;
 
-            // If we didn't fill the buffer we need continue reading
-            if bytes_read > 0 && self.read_buffer_cursor != READ_BUFFER_SIZE {
+            // If we didn't fill the buffer we need continue reading, unless the
+            // end of stream is reached: the partial buffer is then decoded
+            if bytes_read > 0 && self.read_buffer_cursor != self.buffer_size {
                 continue;
             }
 
-            let word_size = std::mem::size_of::<D::RawEventType>();
+            let word_size = D::word_size();
 
             // Compute the size
-            let size = self.read_buffer_cursor - (self.read_buffer_cursor % word_size);
-
-            let evts = <[D::RawEventType]>::ref_from_bytes_with_elems(
-                &self.buffer.0[..size],
-                size / word_size,
-            )
-            .unwrap();
+            self.filled_bytes = self.read_buffer_cursor - (self.read_buffer_cursor % word_size);
+            self.decoded_bytes = 0;
 
             // Reset the cursor
             self.read_buffer_cursor = 0;
-
-            self.decoder.decode(evts, &mut self.event_queue);
-            // evts.iter().for_each(); // TODO
+            return true;
         } // end loop{
     }
+
+    /// Decodes the words of the read buffer not decoded yet, or only enough
+    /// of them to reach the max queue size
+    fn decode_pending_words(&mut self) {
+        let word_size = D::word_size();
+        let pending = (self.filled_bytes - self.decoded_bytes) / word_size;
+        let end = self.decoded_bytes + self.words_to_decode(pending) * word_size;
+
+        if self.swap_bytes {
+            swap_word_bytes(
+                &mut self.buffer.as_mut_bytes()[self.decoded_bytes..end],
+                word_size,
+            );
+        }
+        let evts = <[D::RawEventType]>::ref_from_bytes_with_elems(
+            &self.buffer.as_bytes()[self.decoded_bytes..end],
+            (end - self.decoded_bytes) / word_size,
+        )
+        .unwrap();
+        self.stream_offset += (end - self.decoded_bytes) as u64;
+        self.decoded_bytes = end;
+
+        Self::decode_words(
+            &mut self.decoder,
+            evts,
+            &mut self.event_queue,
+            self.cd_only,
+            self.invert_polarity,
+        );
+    }
+
+    /// Number of words to decode out of `available` so that the queue stays
+    /// under the max queue size
+    fn words_to_decode(&self, available: usize) -> usize {
+        if self.max_queue_size == usize::MAX {
+            return available;
+        }
+        let room = self.max_queue_size.saturating_sub(self.event_queue.len());
+        (room / MAX_EVENTS_PER_WORD).max(1).min(available)
+    }
+
+    fn decode_words(
+        decoder: &mut D,
+        words: &[D::RawEventType],
+        event_queue: &mut VecDeque<Event>,
+        cd_only: bool,
+        invert_polarity: bool,
+    ) {
+        let queued = event_queue.len();
+        decoder.decode(words, event_queue);
+        debug_assert!(
+            event_queue
+                .range(queued..)
+                .all(|e| !matches!(e, Event::CD { p, .. } if *p > 1)),
+            "CD polarities must be 0 or 1"
+        );
+        if invert_polarity {
+            for event in event_queue.range_mut(queued..) {
+                if let Event::CD { p, .. } = event {
+                    *p ^= 1;
+                }
+            }
+        }
+        if cd_only {
+            event_queue.retain(Event::is_cd);
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<R: Read, D: EventDecoder> EvtReader<R, D> {
+    /// Decodes the words of `mmap` starting at `offset` in place instead of
+    /// reading them from the source. The words are swapped first like the
+    /// ones of the source, see [`EvtReader::with_endianness`].
+    pub(crate) fn map_words(&mut self, mmap: memmap2::Mmap, offset: usize) {
+        self.mapped = Some(MappedWords {
+            mmap,
+            position: offset,
+            aligned: Vec::new(),
+        });
+    }
+}
+
+impl<R: Read, D: EventDecoder> Iterator for EvtReader<R, D> {
+    type Item = Event;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if !self.event_queue.is_empty() {
+                return self.event_queue.pop_front();
+            }
+            if !self.fill_event_queue() {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Evt2Decoder;
+
+    #[test]
+    fn test_partial_buffer_at_end_of_stream() {
+        // A time high word followed by 200 CD events, with 2 stray bytes at
+        // the end: the event data is neither a multiple of the read buffer
+        // size nor of the word size
+        let mut bytes: Vec<u8> = (0x8u32 << 28).to_le_bytes().to_vec();
+        for x in 0..200u32 {
+            let cd_on = (1 << 28) | ((x % 64) << 22) | (x << 11) | 7;
+            bytes.extend(cd_on.to_le_bytes());
+        }
+        bytes.extend([0xAB, 0xCD]);
+        assert_ne!(bytes.len() % DEFAULT_READ_BUFFER_SIZE, 0);
+
+        let events: Vec<Event> =
+            EvtReader::new(std::io::Cursor::new(bytes), Evt2Decoder::new()).collect();
+        assert_eq!(events.len(), 200);
+        assert_eq!(events.last(), Some(&Event::cd(199, 7, 1, 199 % 64)));
+    }
+
+    #[test]
+    fn test_max_queue_size() {
+        let open = || {
+            let file = std::fs::File::open("data/openeb/gen4_evt3_hand.raw")
+                .expect("Failed to open test file");
+            let mut reader = std::io::BufReader::new(file);
+            crate::parse_header(&mut reader).expect("Failed to parse header");
+            EvtReader::with_capacity(reader, crate::Evt3Decoder::new(), 64 * 1024)
+        };
+        let expected: Vec<Event> = open().collect();
+
+        let max_queue_size = 16;
+        let mut reader = open().with_max_queue_size(max_queue_size);
+        let mut events = Vec::new();
+        while let Some(event) = reader.next() {
+            assert!(reader.event_queue.len() <= max_queue_size + MAX_EVENTS_PER_WORD);
+            events.push(event);
+        }
+        assert_eq!(events, expected);
+    }
+
+    #[test]
+    fn test_save_and_restore_state() {
+        use std::io::{Cursor, Seek, SeekFrom};
+
+        // Rows of events sharing their timestamp, which EVT2.1 vectorizes
+        let events: Vec<Event> = (0..5000u64)
+            .map(|i| {
+                Event::cd(
+                    (i % 40) as u16,
+                    (i / 40 % 30) as u16,
+                    (i % 3 % 2) as u8,
+                    i / 8 * 10,
+                )
+            })
+            .collect();
+        let bytes = crate::synthetic_evt21(&events);
+
+        // Opens the recording at `offset` in its event data
+        let open = |offset: u64| {
+            let mut reader = Cursor::new(bytes.clone());
+            let header = crate::parse_header(&mut reader).expect("Failed to parse header");
+            reader
+                .seek(SeekFrom::Start(header.header_len + offset))
+                .expect("Failed to seek the recording");
+            EvtReader::new(
+                reader,
+                crate::Evt21Decoder::with_width(header.camera_geometry.width),
+            )
+        };
+        assert_eq!(open(0).collect::<Vec<Event>>(), events);
+
+        let mut reader = open(0);
+        let first_half: Vec<Event> = reader.by_ref().take(events.len() / 2 + 3).collect();
+        let state = reader.save_state();
+        assert!(state.stream_offset > 0);
+        #[cfg(feature = "serde")]
+        let state: DecoderState<crate::Evt21Decoder> =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+
+        let mut resumed = open(state.stream_offset);
+        resumed.restore_state(state);
+        let second_half: Vec<Event> = resumed.collect();
+        assert_eq!([first_half, second_half].concat(), events);
+    }
 }
@@ -0,0 +1,220 @@
+use crate::{Event, Evt3Encoder, Frame, RawFileReader, RawFileReaderError, SliceBy};
+use image::{Rgb, RgbImage};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+// Colors of the frame images, as used by the Metavision viewers. Metavision
+// writes them as BGR, these are their RGB values.
+const BACKGROUND_COLOR: Rgb<u8> = Rgb([30, 37, 52]);
+const POSITIVE_COLOR: Rgb<u8> = Rgb([255, 255, 255]);
+const NEGATIVE_COLOR: Rgb<u8> = Rgb([64, 126, 201]);
+
+/// Writes the CD events of the stream as CSV with a `t,x,y,p` header row.
+///
+/// Events are written one at a time as they are pulled from the iterator, so
+/// the whole file is never held in memory. Wrap `out` in a `BufWriter` when
+/// writing to a file.
+pub fn write_csv<W: Write>(events: impl Iterator<Item = Event>, out: &mut W) -> io::Result<()> {
+    writeln!(out, "t,x,y,p")?;
+    for event in events {
+        if let Event::CD { x, y, p, t } = event {
+            writeln!(out, "{},{},{},{}", t, x, y, p)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the external trigger events of the stream as CSV with a `t,id,p` header row.
+pub fn write_triggers_csv<W: Write>(
+    events: impl Iterator<Item = Event>,
+    out: &mut W,
+) -> io::Result<()> {
+    writeln!(out, "t,id,p")?;
+    for event in events {
+        if let Event::ExternalTrigger { id, p, t } = event {
+            writeln!(out, "{},{},{}", t, id, p)?;
+        }
+    }
+    Ok(())
+}
+
+/// Saves the frame as a PNG image.
+///
+/// Pixels with a positive polarity sum are drawn in white and pixels with a
+/// negative sum in blue, on a dark background.
+pub fn save_frame_png(frame: &Frame, path: &Path) -> io::Result<()> {
+    let image = RgbImage::from_fn(frame.width as u32, frame.height as u32, |x, y| match frame
+        .get(x as usize, y as usize)
+    {
+        0 => BACKGROUND_COLOR,
+        sum if sum > 0 => POSITIVE_COLOR,
+        _ => NEGATIVE_COLOR,
+    });
+    image.save(path).map_err(io::Error::other)
+}
+
+/// Splits the recording at `path` into one raw file per slice, written to
+/// `out_dir` as `<file stem>_000000.raw`, `<file stem>_000001.raw`, etc.
+///
+/// The slices are cut as with [`crate::slice_events`], but every event is
+/// kept, triggers included, so that the files add up to the recording. The
+/// events are encoded as EVT3 whatever the format of the recording, with a
+/// header holding its geometry. Returns the paths of the files in order.
+pub fn split_file(
+    path: &Path,
+    out_dir: &Path,
+    by: SliceBy,
+) -> Result<Vec<PathBuf>, RawFileReaderError> {
+    let mut reader = RawFileReader::new(path)?;
+    fs::create_dir_all(out_dir)
+        .map_err(|e| RawFileReaderError::FileWriteError(out_dir.into(), e))?;
+
+    let geometry = reader.header.camera_geometry;
+    let mut header = String::from("% evt 3.0\n% format EVT3\n");
+    if geometry.width != 0 && geometry.height != 0 {
+        header += &format!("% geometry {}x{}\n", geometry.width, geometry.height);
+    }
+    header += "% end\n";
+
+    let (duration_us, max_count) = match by {
+        SliceBy::Time(micros) => (Some(micros), usize::MAX),
+        SliceBy::Count(count) => (None, count.max(1)),
+        SliceBy::Both(micros, count) => (Some(micros), count.max(1)),
+    };
+
+    let stem = path
+        .file_stem()
+        .map_or("slice".into(), |stem| stem.to_string_lossy());
+    let mut events = reader.read_events().peekable();
+    let mut paths = Vec::new();
+    while events.peek().is_some() {
+        let mut bytes = header.clone().into_bytes();
+        let mut encoder = Evt3Encoder::new();
+        let mut start_us = None;
+        let mut count = 0;
+        while count < max_count {
+            let in_slice = |event: &Event| match (event.timestamp(), start_us, duration_us) {
+                (Some(t), Some(start), Some(duration)) => t < start + duration,
+                _ => true,
+            };
+            let Some(event) = events.next_if(in_slice) else {
+                break;
+            };
+            start_us = start_us.or(event.timestamp());
+            encoder.encode(&event, &mut bytes);
+            count += 1;
+        }
+
+        let slice_path = out_dir.join(format!("{}_{:06}.raw", stem, paths.len()));
+        fs::write(&slice_path, bytes)
+            .map_err(|e| RawFileReaderError::FileWriteError(slice_path.clone(), e))?;
+        paths.push(slice_path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EventSlice;
+
+    fn sample_events() -> Vec<Event> {
+        vec![
+            Event::CD {
+                x: 1,
+                y: 2,
+                p: 1,
+                t: 100,
+            },
+            Event::ExternalTrigger {
+                id: 3,
+                p: 0,
+                t: 150,
+            },
+            Event::CD {
+                x: 320,
+                y: 0,
+                p: 0,
+                t: 200,
+            },
+            Event::Unknown(),
+        ]
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut out = Vec::new();
+        write_csv(sample_events().into_iter(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "t,x,y,p\n100,1,2,1\n200,320,0,0\n"
+        );
+    }
+
+    #[test]
+    fn test_write_triggers_csv() {
+        let mut out = Vec::new();
+        write_triggers_csv(sample_events().into_iter(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "t,id,p\n150,3,0\n");
+    }
+
+    #[test]
+    fn test_save_frame_png() {
+        let events = [
+            Event::cd(0, 0, 1, 100),
+            Event::cd(2, 1, 0, 110),
+            Event::cd(1, 0, 1, 120),
+            Event::cd(1, 0, 0, 130),
+        ];
+        let frame = crate::accumulate_frame(events.into_iter(), 3, 2);
+
+        let path = std::env::temp_dir().join(format!("libreeb_frame_{}.png", std::process::id()));
+        save_frame_png(&frame, &path).unwrap();
+        let image = image::open(&path).unwrap().to_rgb8();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(image.dimensions(), (3, 2));
+        let (white, blue, dark) = (Rgb([255, 255, 255]), Rgb([64, 126, 201]), Rgb([30, 37, 52]));
+        assert_eq!(*image.get_pixel(0, 0), white);
+        assert_eq!(*image.get_pixel(2, 1), blue);
+        // Opposite polarities cancel out
+        assert_eq!(*image.get_pixel(1, 0), dark);
+        assert_eq!(*image.get_pixel(0, 1), dark);
+    }
+
+    #[test]
+    fn test_split_file() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let events: Vec<Event> = RawFileReader::new(path)
+            .expect("Failed to open test file")
+            .read_events()
+            .collect();
+        assert!(events.iter().any(Event::is_trigger));
+
+        let out_dir = std::env::temp_dir().join(format!("libreeb_split_{}", std::process::id()));
+        let slice_us = 200_000;
+        let paths = split_file(path, &out_dir, SliceBy::Time(slice_us)).unwrap();
+        // Each slice starts at its first event, the recording has no gap long
+        // enough to make a slice start much after the end of the previous one
+        let first = events.iter().find_map(Event::timestamp).unwrap();
+        let last = events.iter().filter_map(Event::timestamp).max().unwrap();
+        assert_eq!(paths.len() as u64, (last + 1 - first).div_ceil(slice_us));
+
+        let mut split_events = Vec::new();
+        let mut next_start = first;
+        for slice_path in &paths {
+            let mut reader = RawFileReader::new(slice_path).expect("Failed to open slice file");
+            assert_eq!(reader.header.camera_geometry.width, 1280);
+            let slice = EventSlice::from(reader.read_events().collect::<Vec<Event>>());
+            assert!(slice.start_us >= next_start);
+            assert!(slice.end_us <= slice.start_us + slice_us);
+            next_start = slice.start_us + slice_us;
+            split_events.extend(slice);
+        }
+        fs::remove_dir_all(&out_dir).unwrap();
+        assert_eq!(split_events, events);
+    }
+}
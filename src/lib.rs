@@ -1,25 +1,57 @@
-use enum_dispatch::enum_dispatch;
-use evt_reader::EvtReader;
 use facet::Facet;
-use pyo3::prelude::*;
+use numpy::{PyArray1, PyArray2, PyArrayMethods};
+use pyo3::{prelude::*, types::PyDict};
 use std::{
     collections::{HashMap, VecDeque},
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, Seek, Write},
+    iter::Peekable,
     path::{Path, PathBuf},
+    sync::{Mutex, PoisonError},
 };
 use thiserror::Error;
 
 // Re-export decoders as public
+pub use adaptors::*;
+#[cfg(feature = "tokio")]
+pub use async_reader::*;
+pub use dat::*;
 pub use evt2::*;
 pub use evt2_1::*;
 pub use evt3::*;
+pub use evt_reader::{DecoderState, EvtReader, DEFAULT_READ_BUFFER_SIZE};
+pub use export::*;
+pub use multi::*;
+pub use pipeline::*;
+pub use representations::*;
+pub use shared::*;
+pub use soa::*;
+pub use source::*;
+pub use stats::*;
+#[cfg(any(test, feature = "test-util"))]
+pub use testing::*;
 
+pub mod adaptors;
+#[cfg(feature = "tokio")]
+pub mod async_reader;
+pub mod dat;
 pub mod evt2;
 pub mod evt2_1;
 pub mod evt3;
 mod evt_reader;
+pub mod export;
 mod macros;
+pub mod multi;
+#[cfg(feature = "rayon")]
+mod par;
+pub mod pipeline;
+pub mod representations;
+pub mod shared;
+pub mod soa;
+pub mod source;
+pub mod stats;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 
 // Error types
 #[derive(Error, Debug)]
@@ -27,6 +59,9 @@ pub enum RawFileReaderError {
     #[error("Failed to open file at path {0}")]
     FileOpenError(PathBuf, #[source] io::Error),
 
+    #[error("Failed to write file at path {0}")]
+    FileWriteError(PathBuf, #[source] io::Error),
+
     #[error("Failed to read the content of the file")]
     ReadBytesFailed,
 
@@ -45,12 +80,106 @@ pub enum RawFileReaderError {
     #[error("Wasn't able to find the event type in file header")]
     EventTypeNotFound,
 
+    #[error("The file is empty or doesn't start with a header")]
+    HeaderMissing,
+
+    #[error("No input files were given")]
+    NoInputFiles,
+
+    #[error("File {0} doesn't match the first file: {1}")]
+    IncompatibleFile(PathBuf, String),
+
+    #[error("The event source isn't a file and can't be reopened")]
+    SourceNotReopenable,
+
+    #[error("The header of file {0} changed since it was opened")]
+    HeaderChanged(PathBuf),
+
+    #[error("No time high word was found in the event data, the stream may be truncated")]
+    NoTimeBaseFound,
+
+    #[error("The header doesn't give the {0} of the file")]
+    IncompleteHeader(MissingHeaderFields),
+
+    #[error("Decoding was cancelled by the progress callback")]
+    Cancelled,
+
+    #[error("The decoder state was saved from a stream of another event type")]
+    IncompatibleState,
+
     #[error("An unknown error occurred")]
     Unknown,
 }
 
+/// Values missing from a header that must be strict, see
+/// [`RawFileReaderBuilder::strict_header`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MissingHeaderFields {
+    pub geometry: bool,
+    pub endianness: bool,
+}
+
+impl std::fmt::Display for MissingHeaderFields {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.geometry, self.endianness) {
+            (true, true) => write!(f, "geometry and endianness"),
+            (true, false) => write!(f, "geometry"),
+            (false, true) => write!(f, "endianness"),
+            (false, false) => write!(f, "nothing"),
+        }
+    }
+}
+
+/// Errors raised by a decoder while decoding the event stream
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DecodeError {
+    #[error("Unknown event type: {0:#x}")]
+    UnknownEventType(u8),
+    #[error("Vector event at x {x}, y {y} crosses the end of the row")]
+    VectorOutOfRow { x: u16, y: u16 },
+}
+
+/// What a decoder does with words of an unknown or unsupported event type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnknownPolicy {
+    /// Push an [`Event::Unknown`] in the event queue
+    Emit,
+    /// Ignore the word
+    #[default]
+    Skip,
+    /// Stop decoding, the error is available from the decoder
+    Error,
+}
+
+/// Polarity values of the CD events yielded by a reader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PolarityConvention {
+    /// The polarity as decoded, 0 for a negative and 1 for a positive event
+    /// whatever the format
+    #[default]
+    Raw,
+    /// 0 for a positive and 1 for a negative event, the decoded polarity is inverted
+    ZeroIsPositive,
+}
+
+impl PolarityConvention {
+    /// Whether the decoded polarities are inverted
+    pub fn inverts(&self) -> bool {
+        *self == PolarityConvention::ZeroIsPositive
+    }
+}
+
+/// The polarity `p` of CD events is 0 for a negative (OFF) event and 1 for a
+/// positive (ON) event whatever the format of the decoded data.
+///
+/// With the `serde` feature, events are serialized adjacently tagged by their
+/// variant name, e.g. `{"type":"CD","data":{"x":1,"y":2,"p":1,"t":100}}`
 #[pyclass]
 #[derive(Facet, Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "data"))]
 #[repr(u8)]
 pub enum Event {
     CD { x: u16, y: u16, p: u8, t: u64 },
@@ -60,12 +189,12 @@ pub enum Event {
 
 #[pymethods]
 impl Event {
+    /// Timestamp of a CD or external trigger event, in microseconds
     #[getter]
     pub fn timestamp(&self) -> Option<u64> {
-        if let Event::CD { t, .. } = self {
-            Some(*t)
-        } else {
-            None
+        match self {
+            Event::CD { t, .. } | Event::ExternalTrigger { t, .. } => Some(*t),
+            Event::Unknown() => None,
         }
     }
 
@@ -107,32 +236,398 @@ impl Event {
     }
 }
 
-#[enum_dispatch(Iterator)]
+impl Event {
+    /// Creates a CD event
+    pub const fn cd(x: u16, y: u16, p: u8, t: u64) -> Self {
+        Event::CD { x, y, p, t }
+    }
+
+    /// Creates an external trigger event
+    pub const fn trigger(id: u8, p: u8, t: u64) -> Self {
+        Event::ExternalTrigger { id, p, t }
+    }
+
+    /// Whether this is a CD event
+    pub const fn is_cd(&self) -> bool {
+        matches!(self, Event::CD { .. })
+    }
+
+    /// Whether this is an external trigger event
+    pub const fn is_trigger(&self) -> bool {
+        matches!(self, Event::ExternalTrigger { .. })
+    }
+
+    /// `(x, y)` coordinates of a CD event
+    pub const fn coords(&self) -> Option<(u16, u16)> {
+        match *self {
+            Event::CD { x, y, .. } => Some((x, y)),
+            _ => None,
+        }
+    }
+
+    /// `(x, y, p, t)` fields of a CD event
+    pub const fn as_cd(&self) -> Option<(u16, u16, u8, u64)> {
+        match *self {
+            Event::CD { x, y, p, t } => Some((x, y, p, t)),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::CD { x, y, p, t } => write!(f, "CD({},{},{})@{}", x, y, p, t),
+            Event::ExternalTrigger { id, p, t } => write!(f, "Trigger({},{})@{}", id, p, t),
+            Event::Unknown() => write!(f, "Unknown"),
+        }
+    }
+}
+
+/// Events are ordered by timestamp, CD and trigger events alike, and
+/// [`Event::Unknown`] events come first. Events sharing a timestamp are
+/// ordered CD events first, then by their fields, so that the order is
+/// consistent with equality.
+impl Ord for Event {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let key = |event: &Event| match *event {
+            Event::CD { x, y, p, t } => (Some(t), 0, y, x, p),
+            Event::ExternalTrigger { id, p, t } => (Some(t), 1, id as u16, 0, p),
+            Event::Unknown() => (None, 2, 0, 0, 0),
+        };
+        key(self).cmp(&key(other))
+    }
+}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Raw byte stream decoded by a [`DynamicEvtReader`].
+///
+/// Decoding only needs `Send`, to move a reader to another thread. `Sync` is
+/// required as well because [`RawFileReader`] and [`EventIterator`] are
+/// Python classes, which pyo3 requires to be `Sync` so that they can be
+/// referenced from any Python thread. Use a [`SharedEventIterator`] to
+/// iterate over the same events from several threads.
+pub type ByteSource = Box<dyn BufRead + Send + Sync>;
+
+/// Reader of any supported event type, dispatching statically to its decoder
 pub enum DynamicEvtReader {
-    Evt21(EvtReader<BufReader<File>, Evt21Decoder>),
-    Evt3(EvtReader<BufReader<File>, Evt3Decoder>),
+    Evt2(EvtReader<ByteSource, Evt2Decoder>),
+    Evt21(EvtReader<ByteSource, Evt21Decoder>),
+    Evt3(EvtReader<ByteSource, Evt3Decoder>),
+}
+
+impl DynamicEvtReader {
+    /// Creates the reader matching the event type of `header`, `reader` must
+    /// be positioned at the start of the event data
+    pub fn new(
+        reader: ByteSource,
+        header: &RawFileHeader,
+        options: &ReaderOptions,
+    ) -> Result<Self, RawFileReaderError> {
+        let unknown_policy = options.unknown_policy;
+        match header.event_type {
+            RawEventType::Evt2 => {
+                let decoder = Evt2Decoder::new().with_unknown_policy(unknown_policy);
+                Ok(Self::Evt2(options.evt_reader(reader, header, decoder)))
+            }
+            RawEventType::Evt21 => {
+                let decoder = Evt21Decoder::with_width(header.camera_geometry.width)
+                    .with_unknown_policy(unknown_policy)
+                    .with_strict_width(options.strict_width);
+                Ok(Self::Evt21(options.evt_reader(reader, header, decoder)))
+            }
+            RawEventType::Evt3 => {
+                let decoder = Evt3Decoder::with_width(header.camera_geometry.width)
+                    .with_unknown_policy(unknown_policy);
+                Ok(Self::Evt3(options.evt_reader(reader, header, decoder)))
+            }
+            _ => Err(RawFileReaderError::DecoderNotImplemented(header.event_type)),
+        }
+    }
+
+    /// Size of the read buffer in bytes
+    pub fn buffer_size(&self) -> usize {
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader.buffer_size(),
+            DynamicEvtReader::Evt21(reader) => reader.buffer_size(),
+            DynamicEvtReader::Evt3(reader) => reader.buffer_size(),
+        }
+    }
+
+    /// Error that stopped the decoding, if any
+    pub fn decode_error(&self) -> Option<DecodeError> {
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader.decode_error(),
+            DynamicEvtReader::Evt21(reader) => reader.decode_error(),
+            DynamicEvtReader::Evt3(reader) => reader.decode_error(),
+        }
+    }
+
+    /// Number of undecodable words met so far, see [`EvtReader::decode_error_count`]
+    pub fn decode_error_count(&self) -> u64 {
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader.decode_error_count(),
+            DynamicEvtReader::Evt21(reader) => reader.decode_error_count(),
+            DynamicEvtReader::Evt3(reader) => reader.decode_error_count(),
+        }
+    }
+
+    /// Whether no time base was found in the decoded words, see
+    /// [`EvtReader::time_base_missing`]
+    pub fn time_base_missing(&self) -> bool {
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader.time_base_missing(),
+            DynamicEvtReader::Evt21(reader) => reader.time_base_missing(),
+            DynamicEvtReader::Evt3(reader) => reader.time_base_missing(),
+        }
+    }
+
+    /// Decodes the words of `mmap` starting at `offset` in place instead of
+    /// reading the source
+    #[cfg(feature = "mmap")]
+    fn map_words(&mut self, mmap: memmap2::Mmap, offset: usize) {
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader.map_words(mmap, offset),
+            DynamicEvtReader::Evt21(reader) => reader.map_words(mmap, offset),
+            DynamicEvtReader::Evt3(reader) => reader.map_words(mmap, offset),
+        }
+    }
+
+    /// Calls `f` on every remaining event, see [`EvtReader::for_each_event`]
+    pub fn for_each_event<F: FnMut(Event)>(&mut self, f: F) {
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader.for_each_event(f),
+            DynamicEvtReader::Evt21(reader) => reader.for_each_event(f),
+            DynamicEvtReader::Evt3(reader) => reader.for_each_event(f),
+        }
+    }
+
+    /// Raw words following the ones already decoded, see [`EvtReader::raw_words`]
+    pub fn raw_words(&mut self) -> Box<dyn Iterator<Item = (u64, u8)> + '_> {
+        match self {
+            DynamicEvtReader::Evt2(reader) => Box::new(reader.raw_words()),
+            DynamicEvtReader::Evt21(reader) => Box::new(reader.raw_words()),
+            DynamicEvtReader::Evt3(reader) => Box::new(reader.raw_words()),
+        }
+    }
+
+    /// Number of bytes decoded so far, see [`EvtReader::stream_offset`]
+    pub fn stream_offset(&self) -> u64 {
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader.stream_offset(),
+            DynamicEvtReader::Evt21(reader) => reader.stream_offset(),
+            DynamicEvtReader::Evt3(reader) => reader.stream_offset(),
+        }
+    }
+
+    /// Captures the decoding state, see [`EvtReader::save_state`]
+    pub fn save_state(&self) -> ReaderState {
+        match self {
+            DynamicEvtReader::Evt2(reader) => ReaderState::Evt2(reader.save_state()),
+            DynamicEvtReader::Evt21(reader) => ReaderState::Evt21(reader.save_state()),
+            DynamicEvtReader::Evt3(reader) => ReaderState::Evt3(reader.save_state()),
+        }
+    }
+
+    /// Resumes the decoding from `state`, see [`EvtReader::restore_state`].
+    /// Fails if the state was saved from a stream of another event type.
+    pub fn restore_state(&mut self, state: ReaderState) -> Result<(), RawFileReaderError> {
+        match (self, state) {
+            (DynamicEvtReader::Evt2(reader), ReaderState::Evt2(state)) => {
+                reader.restore_state(state)
+            }
+            (DynamicEvtReader::Evt21(reader), ReaderState::Evt21(state)) => {
+                reader.restore_state(state)
+            }
+            (DynamicEvtReader::Evt3(reader), ReaderState::Evt3(state)) => {
+                reader.restore_state(state)
+            }
+            _ => return Err(RawFileReaderError::IncompatibleState),
+        }
+        Ok(())
+    }
+
+    /// Drops the events before `timestamp_us`, see [`EvtReader::skip_before`]
+    pub fn skip_before(&mut self, timestamp_us: u64) {
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader.skip_before(timestamp_us),
+            DynamicEvtReader::Evt21(reader) => reader.skip_before(timestamp_us),
+            DynamicEvtReader::Evt3(reader) => reader.skip_before(timestamp_us),
+        }
+    }
+
+    /// Decodes the rest of the stream and returns checkpoints at least
+    /// `spacing` bytes apart. `data_offset` is the offset in the file of the
+    /// first word of the stream.
+    fn time_checkpoints(&mut self, spacing: u64, data_offset: u64) -> Vec<TimeCheckpoint> {
+        let checkpoint = |timestamp_us, stream_offset: u64, decoder| TimeCheckpoint {
+            timestamp_us,
+            byte_offset: data_offset + stream_offset,
+            decoder,
+        };
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader
+                .checkpoints(spacing)
+                .into_iter()
+                .map(|(t, offset, decoder)| checkpoint(t, offset, CheckpointDecoder::Evt2(decoder)))
+                .collect(),
+            DynamicEvtReader::Evt21(reader) => reader
+                .checkpoints(spacing)
+                .into_iter()
+                .map(|(t, offset, decoder)| {
+                    checkpoint(t, offset, CheckpointDecoder::Evt21(decoder))
+                })
+                .collect(),
+            DynamicEvtReader::Evt3(reader) => reader
+                .checkpoints(spacing)
+                .into_iter()
+                .map(|(t, offset, decoder)| checkpoint(t, offset, CheckpointDecoder::Evt3(decoder)))
+                .collect(),
+        }
+    }
+
+    /// Resumes decoding with the decoder state saved at a checkpoint
+    fn restore_decoder(&mut self, state: &CheckpointDecoder) {
+        match (self, state) {
+            (DynamicEvtReader::Evt2(reader), CheckpointDecoder::Evt2(decoder)) => {
+                *reader.decoder_mut() = decoder.clone()
+            }
+            (DynamicEvtReader::Evt21(reader), CheckpointDecoder::Evt21(decoder)) => {
+                *reader.decoder_mut() = decoder.clone()
+            }
+            (DynamicEvtReader::Evt3(reader), CheckpointDecoder::Evt3(decoder)) => {
+                *reader.decoder_mut() = decoder.clone()
+            }
+            _ => unreachable!("checkpoint of another event type"),
+        }
+    }
+}
+
+/// Decoding state of a [`DynamicEvtReader`] or a [`RawFileReader`], see
+/// [`DecoderState`]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReaderState {
+    Evt2(DecoderState<Evt2Decoder>),
+    Evt21(DecoderState<Evt21Decoder>),
+    Evt3(DecoderState<Evt3Decoder>),
+}
+
+// Decoder of a DynamicEvtReader, saved to resume decoding from a checkpoint
+#[derive(Clone)]
+enum CheckpointDecoder {
+    Evt2(Evt2Decoder),
+    Evt21(Evt21Decoder),
+    Evt3(Evt3Decoder),
+}
+
+// Point of a file the decoding can be resumed from, see RawFileReader::build_time_index
+#[derive(Clone)]
+struct TimeCheckpoint {
+    timestamp_us: u64, // Latest timestamp decoded before the checkpoint
+    byte_offset: u64,  // Offset of the checkpoint in the file
+    decoder: CheckpointDecoder,
+}
+
+impl Iterator for DynamicEvtReader {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            DynamicEvtReader::Evt2(reader) => reader.next(),
+            DynamicEvtReader::Evt21(reader) => reader.next(),
+            DynamicEvtReader::Evt3(reader) => reader.next(),
+        }
+    }
+}
+
+/// Raw word of an event format, as read from the stream
+pub trait RawWord {
+    /// Bits of the word, zero extended to 64 bits
+    fn raw(&self) -> u64;
+    /// Event type nibble of the word
+    fn word_type(&self) -> u8;
 }
 
 pub trait EventDecoder {
     type RawEventType: zerocopy::FromBytes + zerocopy::Immutable + zerocopy::KnownLayout + Copy;
     fn new() -> Self;
+
+    /// Size in bytes of a raw word of the format
+    fn word_size() -> usize
+    where
+        Self: Sized,
+    {
+        std::mem::size_of::<Self::RawEventType>()
+    }
+
+    /// Name of the format, like "EVT3"
+    fn format_name() -> &'static str
+    where
+        Self: Sized;
+
     fn decode(&mut self, raw_event: &[Self::RawEventType], event_queue: &mut VecDeque<Event>);
+
+    /// Error that stopped the decoding, if any. Once set, `decode` doesn't
+    /// produce events anymore.
+    fn decode_error(&self) -> Option<DecodeError> {
+        None
+    }
+
+    /// Number of words of unknown or unsupported event types met so far,
+    /// whatever the [`UnknownPolicy`]
+    fn unknown_word_count(&self) -> u64 {
+        0
+    }
+
+    /// Whether words were decoded without ever finding the time base of the
+    /// stream, in which case no event could be produced
+    fn time_base_missing(&self) -> bool {
+        false
+    }
 }
 
+// Capacity reserved by `RawFileReader::head`, so that a large n doesn't allocate upfront
+const DEFAULT_HEAD_CAPACITY: usize = 1 << 16;
+
 #[pyclass]
 pub struct RawFileReader {
     pub header: RawFileHeader,
-    path: Box<Path>,
-    event_iterator: Box<dyn Iterator<Item = Event> + Send + Sync>,
+    path: Option<Box<Path>>, // None when the source isn't a file
+    options: ReaderOptions,
+    data_size: u64,
+    data_start: u64, // Size of the header, where the event data starts
+    event_iterator: DynamicEvtReader,
+    stream_start: u64, // Offset in the file of the first word of event_iterator
+    time_index: Vec<TimeCheckpoint>,
+    closed: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Endianness {
     Big,
+    #[default]
     Little,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl Endianness {
+    /// Whether this is the byte order of the target
+    pub fn is_native(&self) -> bool {
+        match self {
+            Endianness::Big => cfg!(target_endian = "big"),
+            Endianness::Little => cfg!(target_endian = "little"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RawEventType {
     Evt2,
     Evt21,
@@ -140,23 +635,162 @@ pub enum RawEventType {
     Evt4,
 }
 
-#[derive(Debug)]
+impl RawEventType {
+    /// Size in bytes of a raw word of this format
+    pub fn word_size(&self) -> usize {
+        match self {
+            RawEventType::Evt2 => Evt2Decoder::word_size(),
+            RawEventType::Evt21 => Evt21Decoder::word_size(),
+            RawEventType::Evt3 => Evt3Decoder::word_size(),
+            RawEventType::Evt4 => 4,
+        }
+    }
+}
+
+/// Event types known by the crate, with whether they can be decoded and encoded
+const SUPPORTED_FORMATS: [(RawEventType, bool, bool); 4] = [
+    (RawEventType::Evt2, true, false),
+    (RawEventType::Evt21, true, false),
+    (RawEventType::Evt3, true, true),
+    (RawEventType::Evt4, false, false),
+];
+
+/// Lists the event types with whether this build can decode and encode
+/// them, as `(event_type, decode, encode)`
+pub fn supported_formats() -> &'static [(RawEventType, bool, bool)] {
+    &SUPPORTED_FORMATS
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CameraGeometry {
     pub width: u32,
     pub height: u32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RawFileHeader {
     pub header_dict: HashMap<String, String>,
     pub event_type: RawEventType,
     pub camera_geometry: CameraGeometry,
+    pub endianness: Endianness,
+    /// Whether the header gives the endianness, which is little endian otherwise
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub endianness_declared: bool,
+    /// Size of the header in bytes, which is the offset of the event data
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub header_len: u64,
+}
+
+impl RawFileHeader {
+    /// Number of bits of the timestamps encoded in the raw stream, before
+    /// they wrap around
+    pub fn timestamp_bits(&self) -> u32 {
+        match self.event_type {
+            // 28 bits of time high and 6 bits of time low
+            RawEventType::Evt2 | RawEventType::Evt21 | RawEventType::Evt4 => 34,
+            // 12 bits of time high and 12 bits of time low
+            RawEventType::Evt3 => 24,
+        }
+    }
+
+    /// Recording date, as written by the camera (like `2023-03-29 16:37:46`)
+    pub fn recording_date(&self) -> Option<&str> {
+        self.field("date")
+    }
+
+    /// Serial number of the camera
+    pub fn serial_number(&self) -> Option<&str> {
+        self.field("serial_number")
+    }
+
+    /// Name of the camera integrator, like `Prophesee`
+    pub fn integrator_name(&self) -> Option<&str> {
+        self.field("camera_integrator_name")
+            .or_else(|| self.field("integrator_name"))
+    }
+
+    /// Generation of the sensor, like `4.1` for a Gen4.1 sensor
+    pub fn sensor_generation(&self) -> Option<&str> {
+        self.field("sensor_generation")
+            .or_else(|| self.field("generation"))
+    }
+
+    fn field(&self, key: &str) -> Option<&str> {
+        dict_field(&self.header_dict, key)
+    }
+
+    /// Period in microseconds after which the raw timestamps roll over, about
+    /// 16.78s for EVT3 and 4.77h for EVT2 and EVT2.1
+    pub fn time_rollover_us(&self) -> u64 {
+        1 << self.timestamp_bits()
+    }
+}
+
+/// Value of `key` in a header dict, matching the keys regardless of their
+/// casing as [`parse_header`] does
+fn dict_field<'a>(header_dict: &'a HashMap<String, String>, key: &str) -> Option<&'a str> {
+    header_dict
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.trim())
+}
+
+/// Whether the line at the start of `buffer` is text, as header lines are,
+/// rather than binary event data. The line may be cut by the end of the buffer.
+fn is_header_line(buffer: &[u8]) -> bool {
+    let line = match buffer.iter().position(|&byte| byte == b'\n') {
+        Some(end) => &buffer[..end],
+        None => buffer,
+    };
+    let text = match std::str::from_utf8(line) {
+        Ok(text) => text,
+        // A character cut by the end of the buffer
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&line[..e.valid_up_to()]).unwrap(),
+        Err(_) => return false,
+    };
+    !text
+        .chars()
+        .any(|c| c.is_control() && c != '\t' && c != '\r')
+}
+
+fn parse_dimension(value: &str) -> Result<u32, RawFileReaderError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_e| RawFileReaderError::ParseHeaderFailed)
+}
+
+// Parses a geometry of the form "1280x720"
+fn parse_geometry(value: &str) -> Result<CameraGeometry, RawFileReaderError> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or(RawFileReaderError::ParseHeaderFailed)?;
+    Ok(CameraGeometry {
+        width: parse_dimension(width)?,
+        height: parse_dimension(height)?,
+    })
+}
+
+fn parse_endianness(value: &str) -> Result<Endianness, RawFileReaderError> {
+    match value.trim() {
+        "little" => Ok(Endianness::Little),
+        "big" => Ok(Endianness::Big),
+        _ => Err(RawFileReaderError::ParseHeaderFailed),
+    }
 }
 
-fn parse_header(reader: &mut impl BufRead) -> Result<RawFileHeader, RawFileReaderError> {
+/// Parses the `%` header of a raw file, leaving `reader` at the start of the event data
+pub fn parse_header(reader: &mut impl BufRead) -> Result<RawFileHeader, RawFileReaderError> {
     let mut header_dict: HashMap<String, String> = HashMap::new();
     let mut event_type_string = None;
     let mut event_format_string = None;
+    let mut geometry_string = None;
+    let mut endianness_string = None;
+    let mut header_size_string = None;
+    let mut header_found = false;
+    let mut header_len = 0;
 
     loop {
         // Look at the next char without consuming it
@@ -164,32 +798,55 @@ fn parse_header(reader: &mut impl BufRead) -> Result<RawFileHeader, RawFileReade
             .fill_buf()
             .map_err(|_e| RawFileReaderError::ReadBytesFailed)?; // TODO: Propagate the error
 
-        let next_char = buffer.first().ok_or(RawFileReaderError::ReadBytesFailed)?;
-
-        if *next_char != b'%' {
-            // if next char is not a % it's the end of header section
+        // Without a "% end" line, event data starting with a '%' byte is
+        // told apart from a header line by not being text
+        let next_char = buffer.first();
+        if next_char != Some(&b'%') || !is_header_line(buffer) {
+            if !header_found {
+                // Nothing at all or events without a header
+                return Err(RawFileReaderError::HeaderMissing);
+            }
+            // if next char is not a % it's the end of header section, the
+            // file may also end right after the header
             break;
         }
 
+        header_found = true;
+
         // Read the line
         let mut header_line = String::new();
         reader
             .read_line(&mut header_line)
             .map_err(|_e| RawFileReaderError::ReadBytesFailed)?; // TODO: Propagate the error
+        header_len += header_line.len() as u64;
 
-        let mut parts = header_line.trim_start_matches('%').trim().splitn(2, ' ');
-        let key = parts.next().ok_or(RawFileReaderError::ParseHeaderFailed)?;
-        let maybe_value = parts.next();
-        if let Some(value) = maybe_value {
-            match key {
+        // Keys are matched regardless of their casing, and may be separated
+        // from their value by any whitespace, e.g. "% Format EVT3" or "%format\tEVT3"
+        let line = header_line.trim_start_matches('%').trim();
+        if line.eq_ignore_ascii_case("end") {
+            // Marks the end of the header, the event data may start with a '%' byte
+            break;
+        }
+        let maybe_pair = line
+            .split_once(char::is_whitespace)
+            .map(|(key, value)| (key.trim(), value.trim()));
+        if let Some((key, value)) = maybe_pair {
+            match key.to_lowercase().as_str() {
                 "evt" => {
                     event_type_string = Some(value.to_string());
                 }
-                "geometry" => {}
+                "geometry" => {
+                    geometry_string = Some(value.to_string());
+                }
                 "format" => {
                     event_format_string = Some(value.to_string());
                 }
-                "endianness" => {}
+                "endianness" => {
+                    endianness_string = Some(value.to_string());
+                }
+                "header_size" => {
+                    header_size_string = Some(value.to_string());
+                }
                 _ => {}
             }
             header_dict.insert(key.to_string(), value.to_string());
@@ -207,13 +864,60 @@ fn parse_header(reader: &mut impl BufRead) -> Result<RawFileHeader, RawFileReade
     // For some reason, some header have a different formating where the
     // format field looks like that: "EVT21;endianness=little;height=320;width=320"
     // in this case we parse that and it takes precedence over other other fields
+    let mut format_width = None;
+    let mut format_height = None;
     if evt_format_str.contains(";") {
         let parts: Vec<String> = evt_format_str.split(";").map(|x| x.to_owned()).collect();
         evt_format_str = parts
             .first()
             .ok_or(RawFileReaderError::ParseHeaderFailed)?
             .to_string();
-        // TODO: deal with other parts of this ;-separated header
+        for part in parts.iter().skip(1) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or(RawFileReaderError::ParseHeaderFailed)?;
+            match key {
+                "width" => format_width = Some(parse_dimension(value)?),
+                "height" => format_height = Some(parse_dimension(value)?),
+                "endianness" => endianness_string = Some(value.to_string()),
+                _ => {}
+            }
+            header_dict.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    let camera_geometry = match (format_width, format_height, geometry_string) {
+        (Some(width), Some(height), _) => CameraGeometry { width, height },
+        (_, _, Some(geometry)) => parse_geometry(&geometry)?,
+        // Guess the geometry from the sensor generation as a last resort
+        _ => dict_field(&header_dict, "sensor_generation")
+            .or_else(|| dict_field(&header_dict, "generation"))
+            .and_then(default_geometry_for)
+            .unwrap_or(CameraGeometry {
+                width: 0,
+                height: 0,
+            }),
+    };
+
+    let endianness_declared = endianness_string.is_some();
+    let endianness = match endianness_string {
+        Some(endianness) => parse_endianness(&endianness)?,
+        None => Endianness::default(),
+    };
+
+    // The header may declare its size, padding included, in which case the
+    // event data starts right after it. A stream ending in the padding has
+    // no events.
+    if let Some(header_size) = header_size_string {
+        let header_size: u64 = header_size
+            .parse()
+            .map_err(|_e| RawFileReaderError::ParseHeaderFailed)?;
+        let padding = header_size
+            .checked_sub(header_len)
+            .ok_or(RawFileReaderError::ParseHeaderFailed)?;
+        io::copy(&mut io::Read::take(&mut *reader, padding), &mut io::sink())
+            .map_err(|_e| RawFileReaderError::ReadBytesFailed)?;
+        header_len = header_size;
     }
 
     let event_type = match evt_format_str.as_str() {
@@ -229,14 +933,57 @@ fn parse_header(reader: &mut impl BufRead) -> Result<RawFileHeader, RawFileReade
     let header = RawFileHeader {
         header_dict,
         event_type,
-        camera_geometry: CameraGeometry {
-            width: 0,
-            height: 0,
-        },
+        camera_geometry,
+        endianness,
+        endianness_declared,
+        header_len,
     };
     Ok(header)
 }
 
+/// Resolution of the Prophesee sensors of a given generation, like `4.1`.
+///
+/// This is a heuristic used by [`parse_header`] only when the header has no
+/// geometry: a camera may crop the sensor, or use a sensor of the same
+/// generation with another resolution.
+pub fn default_geometry_for(generation: &str) -> Option<CameraGeometry> {
+    let (width, height) = match generation.trim() {
+        // Gen3 ATIS
+        "3.0" => (480, 360),
+        // Gen3.1 VGA
+        "3.1" => (640, 480),
+        // Gen4 HD, including the IMX636
+        "4.0" | "4.1" | "4.2" => (1280, 720),
+        _ => return None,
+    };
+    Some(CameraGeometry { width, height })
+}
+
+/// Reads the header of the raw file at `path` without decoding any event,
+/// the file is closed right away
+pub fn read_header(path: &Path) -> Result<RawFileHeader, RawFileReaderError> {
+    let file = File::open(path).map_err(|e| RawFileReaderError::FileOpenError(path.into(), e))?;
+    parse_header(&mut BufReader::new(file))
+}
+
+/// Reads the header of the raw file at `path`, see [`read_header`].
+///
+/// Returns a dict with the `event_type`, `width`, `height` and `endianness`
+/// of the file, and all the header fields under `fields`.
+#[pyfunction]
+#[pyo3(name = "read_header")]
+fn py_read_header<'py>(py: Python<'py>, path: &str) -> PyResult<Bound<'py, PyDict>> {
+    let header = read_header(Path::new(path))
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("{}", e)))?;
+    let dict = PyDict::new(py);
+    dict.set_item("event_type", format!("{:?}", header.event_type))?;
+    dict.set_item("width", header.camera_geometry.width)?;
+    dict.set_item("height", header.camera_geometry.height)?;
+    dict.set_item("endianness", format!("{:?}", header.endianness))?;
+    dict.set_item("fields", header.header_dict)?;
+    Ok(dict)
+}
+
 #[pymethods]
 impl RawFileReader {
     #[new]
@@ -247,92 +994,825 @@ impl RawFileReader {
     }
 
     pub fn get_event_iterator(&self) -> PyResult<EventIterator> {
-        let file = File::open(&self.path).map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyIOError, _>(format!("Failed to open file: {}", e))
-        })?;
-        let mut reader = BufReader::with_capacity(64 * 1024, file);
-        let _header = parse_header(&mut reader).map_err(|e| {
-            PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!(
-                "Failed to parse header: {}",
-                e
-            ))
-        })?;
-
-        let event_iterator: Box<dyn Iterator<Item = Event> + Send + Sync> =
-            match self.header.event_type {
-                RawEventType::Evt21 => {
-                    let decoder = Evt21Decoder::new();
-                    Box::new(EvtReader::new(reader, decoder))
-                }
-                RawEventType::Evt3 => {
-                    let decoder = Evt3Decoder::new();
-                    Box::new(EvtReader::new(reader, decoder))
-                }
-                _ => {
-                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
-                        "Unsupported event type",
-                    ))
-                }
-            };
+        if self.closed {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "I/O operation on a closed reader",
+            ));
+        }
+        let event_iterator = self.reopen_events().map_err(reopen_error)?;
 
         Ok(EventIterator {
             inner: event_iterator,
+            pipeline: Mutex::new(EventPipeline::new()),
+            geometry: self.header.camera_geometry,
+            emitted: 0,
+            estimate: self.estimate_event_count(),
         })
     }
 
-    // pub fn read_events_py<'a>(&'a mut self) -> EventIterator {
-    //     EventIterator {
-    //         inter: self.event_iterator,
-    //     }
-    // }
-}
+    /// Estimated number of events, see [`RawFileReader::estimate_event_count`].
+    ///
+    /// The real count is only known after decoding the whole file, so this
+    /// must not be relied upon to be exact.
+    fn __len__(&self) -> usize {
+        self.estimate_event_count() as usize
+    }
 
-impl RawFileReader {
-    pub fn new(path: &Path) -> Result<Self, RawFileReaderError> {
-        let file =
-            File::open(path).map_err(|e| RawFileReaderError::FileOpenError(path.into(), e))?;
+    /// Iterates over the frames of `window_us` microseconds of the file, see
+    /// [`frame_stream`]. Each frame is a `(height, width)` numpy array of
+    /// signed polarity sums.
+    pub fn frame_stream(&self, window_us: u64) -> PyResult<FrameIterator> {
+        let events = self.get_event_iterator()?.inner;
+        let width = self.header.camera_geometry.width as usize;
+        let height = self.header.camera_geometry.height as usize;
+        Ok(FrameIterator {
+            inner: Box::new(frame_stream(events, width, height, window_us)),
+        })
+    }
 
-        let mut reader = BufReader::with_capacity(64 * 1024, file);
+    /// Accumulates the CD events of the file from `start_us` and for
+    /// `duration_us` microseconds into a frame, see [`accumulate_frame`].
+    /// Returns a `(height, width)` numpy array of signed polarity sums.
+    ///
+    /// The file is decoded independently of this reader, which keeps its
+    /// position, from the last checkpoint before `start_us` once
+    /// `build_time_index` is called. Raises a `ValueError` when the header
+    /// doesn't give the sensor geometry.
+    pub fn accumulate_frame<'py>(
+        &self,
+        py: Python<'py>,
+        start_us: u64,
+        duration_us: u64,
+    ) -> PyResult<Bound<'py, PyArray2<i32>>> {
+        if self.closed {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "I/O operation on a closed reader",
+            ));
+        }
+        let width = self.header.camera_geometry.width as usize;
+        let height = self.header.camera_geometry.height as usize;
+        if width == 0 || height == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "The file header doesn't provide the sensor geometry",
+            ));
+        }
+        let events = self.reopen_events_from(start_us).map_err(reopen_error)?;
+        let end_us = start_us.saturating_add(duration_us);
+        let window = events.take_while(|event| event.timestamp().is_none_or(|t| t < end_us));
+        let frame = accumulate_frame(window, width, height);
+        PyArray1::from_vec(py, frame.data).reshape([height, width])
+    }
 
-        let header = parse_header(&mut reader)?;
+    /// Indexes the file to seek in it quickly, see
+    /// [`RawFileReader::build_time_index`]. Returns the checkpoints as
+    /// `(timestamp_us, byte_offset)` tuples.
+    #[pyo3(name = "build_time_index", signature = (n_buckets=100))]
+    fn py_build_time_index(&mut self, n_buckets: usize) -> PyResult<Vec<(u64, u64)>> {
+        self.build_time_index(n_buckets).map_err(reopen_error)
+    }
 
-        let event_iterator: Box<dyn Iterator<Item = Event> + Send + Sync> = match header.event_type
-        {
-            RawEventType::Evt2 => {
-                let becoder = Evt2Decoder::new();
-                Box::new(EvtReader::new(reader, becoder))
-            }
-            RawEventType::Evt21 => {
-                let becoder = Evt21Decoder::new();
-                Box::new(EvtReader::new(reader, becoder))
+    /// Iterates over the CD events of the file by batches of `n_events`
+    /// events or `delta_t_us` microseconds, one of which must be given. Each
+    /// batch is a numpy structured array, see [`RawFileReader::to_numpy`].
+    #[pyo3(signature = (n_events=None, delta_t_us=None))]
+    pub fn batches(
+        &self,
+        n_events: Option<usize>,
+        delta_t_us: Option<u64>,
+    ) -> PyResult<BatchIterator> {
+        let batch_size = match (n_events, delta_t_us) {
+            (Some(n_events), None) if n_events > 0 => BatchSize::Events(n_events),
+            (None, Some(delta_t_us)) if delta_t_us > 0 => BatchSize::Time(delta_t_us),
+            _ => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                    "Expected a positive n_events or delta_t_us, but not both",
+                ))
             }
-            RawEventType::Evt3 => {
-                let becoder = Evt3Decoder::new();
-                Box::new(EvtReader::new(reader, becoder))
-            }
-            _ => return Err(RawFileReaderError::DecoderNotImplemented(header.event_type)),
         };
+        Ok(BatchIterator {
+            inner: self.get_event_iterator()?.inner,
+            batch_size,
+            pending: None,
+            buffer: Vec::new(),
+        })
+    }
 
-        Ok(RawFileReader {
+    /// Consumes the remaining events of the file and returns its CD events as
+    /// a numpy structured array with the `x`, `y`, `p` and `t` fields of
+    /// Metavision's `EventCD` (`u2`, `u2`, `i2` and `i8`).
+    ///
+    /// `progress` is called with `(done, total)` while decoding.
+    #[pyo3(signature = (progress=None))]
+    pub fn to_numpy<'py>(
+        &mut self,
+        py: Python<'py>,
+        progress: Option<&Bound<'py, PyAny>>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let total = self.estimate_event_count();
+        let events: Vec<Event> = with_py_progress(progress, |progress| match progress {
+            Some(progress) => report_progress(self.read_events(), total, progress)
+                .filter(Event::is_cd)
+                .collect(),
+            None => self.read_events().filter(Event::is_cd).collect(),
+        })?;
+        cd_events_to_numpy(py, &events)
+    }
+
+    /// Same as [`RawFileReader::to_numpy`] but only the CD events inside the
+    /// `[x0, x1) x [y0, y1)` region of interest are returned, see [`crop`].
+    ///
+    /// With `rebase`, the coordinates are relative to the `(x0, y0)` corner.
+    #[pyo3(signature = (x0, y0, x1, y1, rebase=false))]
+    pub fn to_numpy_roi<'py>(
+        &mut self,
+        py: Python<'py>,
+        x0: u16,
+        y0: u16,
+        x1: u16,
+        y1: u16,
+        rebase: bool,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let events: Vec<Event> = crop(self.read_events(), x0, y0, x1, y1, rebase)
+            .filter(Event::is_cd)
+            .collect();
+        cd_events_to_numpy(py, &events)
+    }
+
+    /// Decodes the next `n` events, see [`RawFileReader::head`]. `progress`
+    /// is called with `(done, total)` while decoding.
+    #[pyo3(name = "head", signature = (n, progress=None))]
+    fn py_head(&mut self, n: usize, progress: Option<&Bound<'_, PyAny>>) -> PyResult<Vec<Event>> {
+        with_py_progress(progress, |progress| self.head(n, progress))
+    }
+
+    /// Consumes the remaining events of the file and summarizes them
+    pub fn event_stats(&mut self) -> EventStats {
+        event_stats(self.read_events())
+    }
+
+    /// Consumes the remaining events of the file and counts them by type
+    pub fn count_by_type(&mut self) -> EventTypeCounts {
+        count_by_type(self.read_events())
+    }
+
+    /// Consumes the remaining events of the file and counts the CD events of
+    /// each pixel, see [`event_count_map`]. Returns a `(height, width)` numpy
+    /// array.
+    #[pyo3(name = "event_count_map")]
+    fn py_event_count_map<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<u32>>> {
+        let width = self.header.camera_geometry.width as usize;
+        let height = self.header.camera_geometry.height as usize;
+        let counts = event_count_map(self.read_events(), width, height);
+        PyArray1::from_vec(py, counts).reshape([height, width])
+    }
+
+    /// Estimates the number of events of the file from its size, without decoding it.
+    ///
+    /// This is the number of raw words after the header. Time high and trigger
+    /// words make it an overestimate of the CD events, while vectorized words
+    /// (EVT2.1, EVT3) can make it an underestimate, so only use it for progress
+    /// reporting or as a capacity hint.
+    pub fn estimate_event_count(&self) -> u64 {
+        self.data_size / self.header.event_type.word_size() as u64
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        _exc_type: &Bound<'_, PyAny>,
+        _exc_value: &Bound<'_, PyAny>,
+        _traceback: &Bound<'_, PyAny>,
+    ) -> bool {
+        self.close();
+        // Don't swallow the exception raised in the `with` block, if any
+        false
+    }
+
+    /// Releases the file handle of the reader, it yields no more events and
+    /// can't create event iterators afterwards.
+    ///
+    /// The iterators already created own their file handle, which is released
+    /// when they are dropped.
+    pub fn close(&mut self) {
+        if let Ok(empty) = DynamicEvtReader::new(Box::new(io::empty()), &self.header, &self.options)
+        {
+            self.event_iterator = empty;
+        }
+        self.closed = true;
+    }
+
+    #[getter]
+    pub fn closed(&self) -> bool {
+        self.closed
+    }
+
+    // pub fn read_events_py<'a>(&'a mut self) -> EventIterator {
+    //     EventIterator {
+    //         inter: self.event_iterator,
+    //     }
+    // }
+}
+
+impl RawFileReader {
+    pub fn new(path: &Path) -> Result<Self, RawFileReaderError> {
+        Self::builder(path).build()
+    }
+
+    /// Opens the file at `path` using the given sensor size instead of the
+    /// geometry of the header, see [`RawFileReaderBuilder::geometry`]
+    pub fn new_with_geometry(
+        path: &Path,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, RawFileReaderError> {
+        Self::builder(path).geometry(width, height).build()
+    }
+
+    /// Opens the file at `path` with bounds on the decoding work, see
+    /// [`RawFileReaderBuilder::max_events`] and
+    /// [`RawFileReaderBuilder::max_duration_us`]
+    pub fn new_capped(
+        path: &Path,
+        max_events: Option<usize>,
+        max_duration_us: Option<u64>,
+    ) -> Result<Self, RawFileReaderError> {
+        let mut builder = Self::builder(path);
+        builder.options.max_events = max_events;
+        builder.options.max_duration_us = max_duration_us;
+        builder.build()
+    }
+
+    /// Opens the file at `path` and decodes its events from a memory map,
+    /// without copying the raw data into a read buffer. This is faster on
+    /// large recordings.
+    ///
+    /// The file must not be modified while it is being decoded.
+    #[cfg(feature = "mmap")]
+    pub fn new_mmap(path: &Path) -> Result<Self, RawFileReaderError> {
+        Self::builder(path).mmap(true).build()
+    }
+
+    /// Starts configuring a reader of the file at `path`
+    pub fn builder(path: &Path) -> RawFileReaderBuilder {
+        RawFileReaderBuilder {
             path: path.into(),
+            options: ReaderOptions::default(),
+        }
+    }
+
+    /// Creates a reader decoding the raw data from any buffered source, like
+    /// `std::io::stdin()`. The header must be at the start of the stream.
+    ///
+    /// Such a reader can't be reset and [`RawFileReader::estimate_event_count`] returns 0.
+    pub fn from_bufread(
+        reader: impl BufRead + Send + Sync + 'static,
+    ) -> Result<Self, RawFileReaderError> {
+        let mut reader: ByteSource = Box::new(reader);
+        let header = parse_header(&mut reader)?;
+        let options = ReaderOptions::default();
+        let event_iterator = DynamicEvtReader::new(reader, &header, &options)?;
+        Ok(RawFileReader {
+            path: None,
+            options,
+            data_size: 0,
+            data_start: 0,
             event_iterator,
+            stream_start: 0,
+            time_index: Vec::new(),
             header,
+            closed: false,
         })
     }
 
+    /// Reopens the file and decodes it from the first event, independently
+    /// of this reader.
+    ///
+    /// The header is parsed again and must match the one read when the reader
+    /// was created, otherwise the file changed in between and decoding it with
+    /// the stored header would give garbage.
+    fn reopen_events(&self) -> Result<DynamicEvtReader, RawFileReaderError> {
+        self.reopen_events_at(None, &self.options)
+    }
+
+    /// Same as [`RawFileReader::reopen_events`] with other options, decoding
+    /// the file from `offset` instead of the first event when it's set
+    fn reopen_events_at(
+        &self,
+        offset: Option<u64>,
+        options: &ReaderOptions,
+    ) -> Result<DynamicEvtReader, RawFileReaderError> {
+        let path = self
+            .path
+            .as_deref()
+            .ok_or(RawFileReaderError::SourceNotReopenable)?;
+        let file =
+            File::open(path).map_err(|e| RawFileReaderError::FileOpenError(path.into(), e))?;
+        let mut reader = BufReader::with_capacity(64 * 1024, file);
+        let mut header = parse_header(&mut reader)?;
+        self.options.apply_overrides(&mut header);
+        if header != self.header {
+            return Err(RawFileReaderError::HeaderChanged(path.into()));
+        }
+        if let Some(offset) = offset {
+            reader
+                .seek(io::SeekFrom::Start(offset))
+                .map_err(|_e| RawFileReaderError::ReadBytesFailed)?;
+        }
+        options.file_events(reader, &self.header)
+    }
+
+    /// Error that stopped the decoding of the file, if any. Only happens with
+    /// [`UnknownPolicy::Error`].
+    pub fn decode_error(&self) -> Option<DecodeError> {
+        self.event_iterator.decode_error()
+    }
+
+    /// Number of words of unknown event types decoded so far, which are
+    /// skipped by default. Check it after iterating to assess the integrity
+    /// of the file.
+    pub fn decode_error_count(&self) -> u64 {
+        self.event_iterator.decode_error_count()
+    }
+
+    /// Fails with [`RawFileReaderError::NoTimeBaseFound`] when the events
+    /// decoded so far had no time high word. The decoders wait for one before
+    /// producing events, so a stream starting in the middle of the data
+    /// yields no events at all: call this after iterating to tell it apart
+    /// from an empty recording.
+    pub fn check_time_base(&self) -> Result<(), RawFileReaderError> {
+        match self.event_iterator.time_base_missing() {
+            true => Err(RawFileReaderError::NoTimeBaseFound),
+            false => Ok(()),
+        }
+    }
+
     // TODO: rename this function
-    pub fn read_events<'a>(&'a mut self) -> Box<dyn std::iter::Iterator<Item = Event> + 'a> {
-        Box::new(&mut self.event_iterator)
+    pub fn read_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        &mut self.event_iterator
+    }
+
+    /// Yields the CD and trigger events with a timestamp in
+    /// `[start_us, end_us)`, starting from the current position.
+    ///
+    /// The reader first seeks to `start_us` with [`RawFileReader::seek_to`],
+    /// which jumps close to it once [`RawFileReader::build_time_index`] is
+    /// called. The iteration stops at the first event after the range, which
+    /// is consumed. An empty range or one past the end of the file yields
+    /// nothing.
+    pub fn read_events_between(
+        &mut self,
+        start_us: u64,
+        end_us: u64,
+    ) -> Result<impl Iterator<Item = Event> + '_, RawFileReaderError> {
+        self.seek_to(start_us)?;
+        let end_us = end_us.max(start_us);
+        Ok(self
+            .read_events()
+            .filter_map(|event| Some((event.timestamp()?, event)))
+            .take_while(move |&(t, _)| t < end_us)
+            .map(|(_, event)| event))
+    }
+
+    /// Calls `f` on every remaining event of the file.
+    ///
+    /// This yields the same events as [`RawFileReader::read_events`] but
+    /// without going through the iterator for each event, which is faster in
+    /// hot loops.
+    pub fn for_each_event<F: FnMut(Event)>(&mut self, f: F) {
+        self.event_iterator.for_each_event(f)
+    }
+
+    /// Iterates over the raw words of the file as `(raw, event_type)`
+    /// without decoding them, to inspect the time high, time low or vector
+    /// words directly.
+    ///
+    /// This consumes the same stream as [`RawFileReader::read_events`], call
+    /// [`RawFileReader::reset`] to decode the file afterwards.
+    pub fn raw_words(&mut self) -> impl Iterator<Item = (u64, u8)> + '_ {
+        self.event_iterator.raw_words()
+    }
+
+    /// Decodes the next `n` events, or less at the end of the file.
+    ///
+    /// Only the raw data needed to produce them is read, which makes it cheap
+    /// to preview huge files. `progress` is called with `(done, total)` while
+    /// decoding, see [`report_progress`].
+    pub fn head(&mut self, n: usize, progress: Option<&mut dyn FnMut(u64, u64)>) -> Vec<Event> {
+        let mut events = Vec::with_capacity(n.min(DEFAULT_HEAD_CAPACITY));
+        let total = (n as u64).min(self.estimate_event_count());
+        match progress {
+            Some(progress) => {
+                events.extend(report_progress(self.read_events().take(n), total, progress))
+            }
+            None => events.extend(self.read_events().take(n)),
+        }
+        events
+    }
+
+    /// Writes the remaining CD events of the file as CSV, see [`write_csv`].
+    ///
+    /// `progress` is called with `(done, total)` while decoding, see
+    /// [`report_progress`].
+    pub fn write_csv<W: Write>(
+        &mut self,
+        out: &mut W,
+        progress: Option<&mut dyn FnMut(u64, u64)>,
+    ) -> io::Result<()> {
+        let total = self.estimate_event_count();
+        match progress {
+            Some(progress) => write_csv(report_progress(self.read_events(), total, progress), out),
+            None => write_csv(self.read_events(), out),
+        }
+    }
+
+    /// Resets the file reader, which fails if the source isn't a file. The
+    /// time index, if any, is kept.
+    pub fn reset(&mut self) -> Result<(), RawFileReaderError> {
+        let Some(path) = &self.path else {
+            return Err(RawFileReaderError::SourceNotReopenable);
+        };
+        let mut reader = RawFileReaderBuilder {
+            path: path.to_path_buf(),
+            options: self.options.clone(),
+        }
+        .build()?;
+        reader.time_index = std::mem::take(&mut self.time_index);
+        *self = reader;
+        Ok(())
+    }
+
+    /// Decodes the whole file in a separate pass and indexes the points the
+    /// decoding can be resumed from, so that [`RawFileReader::seek_to`] can
+    /// jump close to its target instead of decoding everything before it.
+    ///
+    /// The file is cut in `n_buckets` parts holding the same amount of event
+    /// data, which puts more checkpoints in the busy parts of the recording.
+    /// Returns the checkpoints as `(timestamp_us, byte_offset)`, where
+    /// `timestamp_us` is the latest timestamp decoded before the offset in the
+    /// file `byte_offset`. The index is kept by the reader, and the position
+    /// of the reader is unchanged. Fails if the source isn't a file.
+    pub fn build_time_index(
+        &mut self,
+        n_buckets: usize,
+    ) -> Result<Vec<(u64, u64)>, RawFileReaderError> {
+        let options = ReaderOptions {
+            max_events: None,
+            max_duration_us: None,
+            ..self.options.clone()
+        };
+        let mut events = self.reopen_events_at(None, &options)?;
+        let spacing = (self.data_size / n_buckets.max(1) as u64).max(1);
+        self.time_index = events.time_checkpoints(spacing, self.data_start);
+        Ok(self
+            .time_index
+            .iter()
+            .map(|checkpoint| (checkpoint.timestamp_us, checkpoint.byte_offset))
+            .collect())
+    }
+
+    /// Skips the events before the first CD or trigger event at or after
+    /// `timestamp_us`, starting from the current position.
+    ///
+    /// Once [`RawFileReader::build_time_index`] is called, the file is
+    /// reopened at the last checkpoint before the target when it's ahead of
+    /// the current position, the max events and max duration limits then
+    /// restart from there. Otherwise every event before the target is decoded
+    /// and dropped.
+    pub fn seek_to(&mut self, timestamp_us: u64) -> Result<(), RawFileReaderError> {
+        let position = self.stream_start + self.event_iterator.stream_offset();
+        let checkpoint = self
+            .checkpoint_before(timestamp_us)
+            .filter(|checkpoint| checkpoint.byte_offset > position && !self.closed);
+        if let Some(checkpoint) = checkpoint {
+            let events = self.reopen_events_at_checkpoint(checkpoint)?;
+            self.stream_start = checkpoint.byte_offset;
+            self.event_iterator = events;
+        }
+        self.event_iterator.skip_before(timestamp_us);
+        Ok(())
+    }
+
+    /// Same as [`RawFileReader::seek_to`] on a new iterator over the file,
+    /// independent of this reader
+    fn reopen_events_from(
+        &self,
+        timestamp_us: u64,
+    ) -> Result<DynamicEvtReader, RawFileReaderError> {
+        let mut events = match self.checkpoint_before(timestamp_us) {
+            Some(checkpoint) => self.reopen_events_at_checkpoint(checkpoint)?,
+            None => self.reopen_events()?,
+        };
+        events.skip_before(timestamp_us);
+        Ok(events)
+    }
+
+    /// Last checkpoint of the time index before `timestamp_us`
+    fn checkpoint_before(&self, timestamp_us: u64) -> Option<&TimeCheckpoint> {
+        self.time_index
+            .iter()
+            .rev()
+            .find(|checkpoint| checkpoint.timestamp_us < timestamp_us)
+    }
+
+    fn reopen_events_at_checkpoint(
+        &self,
+        checkpoint: &TimeCheckpoint,
+    ) -> Result<DynamicEvtReader, RawFileReaderError> {
+        let mut events = self.reopen_events_at(Some(checkpoint.byte_offset), &self.options)?;
+        events.restore_decoder(&checkpoint.decoder);
+        Ok(events)
+    }
+
+    /// Captures the decoding state, to resume it with
+    /// [`RawFileReader::restore_state`] on a reader of the same file, possibly
+    /// in another process. The offset of the state is counted from the start
+    /// of the event data.
+    pub fn save_state(&self) -> ReaderState {
+        let offset = self.stream_start - self.data_start;
+        let mut state = self.event_iterator.save_state();
+        match &mut state {
+            ReaderState::Evt2(state) => state.stream_offset += offset,
+            ReaderState::Evt21(state) => state.stream_offset += offset,
+            ReaderState::Evt3(state) => state.stream_offset += offset,
+        }
+        state
+    }
+
+    /// Resumes the decoding from a state saved by [`RawFileReader::save_state`].
+    ///
+    /// The file is reopened at the offset of the state, as with
+    /// [`RawFileReader::reset`], and the max events and max duration limits
+    /// restart from there. Fails if the source isn't a file or if the state
+    /// was saved from a file of another event type.
+    pub fn restore_state(&mut self, state: ReaderState) -> Result<(), RawFileReaderError> {
+        let offset = match &state {
+            ReaderState::Evt2(state) => state.stream_offset,
+            ReaderState::Evt21(state) => state.stream_offset,
+            ReaderState::Evt3(state) => state.stream_offset,
+        };
+        let mut events = self.reopen_events_at(Some(self.data_start + offset), &self.options)?;
+        events.restore_state(state)?;
+        self.stream_start = self.data_start;
+        self.event_iterator = events;
+        self.closed = false;
+        Ok(())
+    }
+
+    /// Restarts the file at the first CD or trigger event at or after
+    /// `timestamp_us`, e.g. to replay a segment of the recording in a loop.
+    /// See [`RawFileReader::reset`] and [`RawFileReader::seek_to`].
+    pub fn reset_to(&mut self, timestamp_us: u64) -> Result<(), RawFileReaderError> {
+        self.reset()?;
+        self.seek_to(timestamp_us)
+    }
+}
+
+/// Decoding options of a [`RawFileReader`], set through [`RawFileReaderBuilder`]
+#[derive(Debug, Clone)]
+pub struct ReaderOptions {
+    pub unknown_policy: UnknownPolicy,
+    /// Overrides the geometry of the header
+    pub geometry: Option<CameraGeometry>,
+    /// Overrides the endianness of the header, which sets the byte order
+    /// of the raw words
+    pub endianness: Option<Endianness>,
+    /// Size of the read buffer in bytes
+    pub buffer_size: usize,
+    /// Only yield CD events
+    pub cd_only: bool,
+    /// Polarity values of the CD events
+    pub polarity_convention: PolarityConvention,
+    /// Stop with an error on an EVT2.1 vector event crossing the end of the row
+    pub strict_width: bool,
+    /// Fail to open a file whose header misses the geometry or the endianness
+    pub strict_header: bool,
+    /// Stop after this many events
+    pub max_events: Option<usize>,
+    /// Stop at the first event this many microseconds after the first one
+    pub max_duration_us: Option<u64>,
+    /// Decode the file from a memory map instead of reading it
+    #[cfg(feature = "mmap")]
+    pub mmap: bool,
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self {
+        ReaderOptions {
+            unknown_policy: UnknownPolicy::default(),
+            geometry: None,
+            endianness: None,
+            buffer_size: DEFAULT_READ_BUFFER_SIZE,
+            cd_only: false,
+            polarity_convention: PolarityConvention::default(),
+            strict_width: false,
+            strict_header: false,
+            max_events: None,
+            max_duration_us: None,
+            #[cfg(feature = "mmap")]
+            mmap: false,
+        }
+    }
+}
+
+impl ReaderOptions {
+    fn evt_reader<D: EventDecoder>(
+        &self,
+        reader: ByteSource,
+        header: &RawFileHeader,
+        decoder: D,
+    ) -> EvtReader<ByteSource, D> {
+        EvtReader::with_capacity(reader, decoder, self.buffer_size)
+            .with_endianness(header.endianness)
+            .with_cd_only(self.cd_only)
+            .with_inverted_polarity(self.polarity_convention.inverts())
+            .with_limits(self.max_events, self.max_duration_us)
+    }
+
+    /// Creates the event reader of a file positioned at the start of its
+    /// event data, decoding it from a memory map with the `mmap` option
+    fn file_events(
+        &self,
+        reader: BufReader<File>,
+        header: &RawFileHeader,
+    ) -> Result<DynamicEvtReader, RawFileReaderError> {
+        #[cfg(feature = "mmap")]
+        if self.mmap {
+            let mut reader = reader;
+            let offset = reader
+                .stream_position()
+                .map_err(|_e| RawFileReaderError::ReadBytesFailed)?;
+            // SAFETY: as with any memory map, the file must not be truncated
+            // or modified by another process while it is decoded
+            let mmap = unsafe { memmap2::Mmap::map(reader.get_ref()) }
+                .map_err(|_e| RawFileReaderError::ReadBytesFailed)?;
+            let mut events = DynamicEvtReader::new(Box::new(io::empty()), header, self)?;
+            events.map_words(mmap, offset as usize);
+            return Ok(events);
+        }
+        DynamicEvtReader::new(Box::new(reader), header, self)
+    }
+
+    /// Checks that the geometry and the endianness are known, either from
+    /// the header or forced by the options, when the header must be strict
+    fn validate_header(&self, header: &RawFileHeader) -> Result<(), RawFileReaderError> {
+        if !self.strict_header {
+            return Ok(());
+        }
+        let geometry = header.camera_geometry;
+        let missing = MissingHeaderFields {
+            geometry: geometry.width == 0 || geometry.height == 0,
+            endianness: self.endianness.is_none() && !header.endianness_declared,
+        };
+        if missing.geometry || missing.endianness {
+            return Err(RawFileReaderError::IncompleteHeader(missing));
+        }
+        Ok(())
+    }
+
+    /// Replaces the values of the file header forced by the options
+    fn apply_overrides(&self, header: &mut RawFileHeader) {
+        if let Some(geometry) = self.geometry {
+            header.camera_geometry = geometry;
+        }
+        if let Some(endianness) = self.endianness {
+            header.endianness = endianness;
+        }
+    }
+}
+
+/// Options to open a [`RawFileReader`], see [`RawFileReader::builder`]
+#[derive(Debug, Clone)]
+pub struct RawFileReaderBuilder {
+    path: PathBuf,
+    options: ReaderOptions,
+}
+
+impl RawFileReaderBuilder {
+    /// Sets how words of unknown event types are handled, [`UnknownPolicy::Skip`] by default
+    pub fn unknown_policy(mut self, unknown_policy: UnknownPolicy) -> Self {
+        self.options.unknown_policy = unknown_policy;
+        self
+    }
+
+    /// Forces the sensor size, for files whose header has a missing or wrong
+    /// geometry. It takes precedence over any geometry found in the header.
+    pub fn geometry(mut self, width: u32, height: u32) -> Self {
+        self.options.geometry = Some(CameraGeometry { width, height });
+        self
+    }
+
+    /// Makes an EVT2.1 vector event crossing the end of the row a decoding
+    /// error, see [`Evt21Decoder::with_strict_width`]. Its events out of the
+    /// sensor are dropped by default.
+    pub fn strict_width(mut self, strict_width: bool) -> Self {
+        self.options.strict_width = strict_width;
+        self
+    }
+
+    /// Makes a header without geometry or endianness an
+    /// [`RawFileReaderError::IncompleteHeader`] error. By default the geometry
+    /// is then 0x0, or guessed from the sensor generation, and the endianness
+    /// little endian. Values forced by [`RawFileReaderBuilder::geometry`] and
+    /// [`RawFileReaderBuilder::endianness`] count as given.
+    pub fn strict_header(mut self, strict_header: bool) -> Self {
+        self.options.strict_header = strict_header;
+        self
+    }
+
+    /// Forces the byte order of the raw words, whatever the header declares
+    pub fn endianness(mut self, endianness: Endianness) -> Self {
+        self.options.endianness = Some(endianness);
+        self
+    }
+
+    /// Sets the size of the read buffer in bytes, [`DEFAULT_READ_BUFFER_SIZE`] by default
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.options.buffer_size = buffer_size;
+        self
+    }
+
+    /// Only yields the CD events, dropping triggers and other events
+    pub fn cd_only(mut self, cd_only: bool) -> Self {
+        self.options.cd_only = cd_only;
+        self
+    }
+
+    /// Sets the polarity values of the CD events, [`PolarityConvention::Raw`] by default
+    pub fn polarity_convention(mut self, polarity_convention: PolarityConvention) -> Self {
+        self.options.polarity_convention = polarity_convention;
+        self
+    }
+
+    /// Stops decoding after `max_events` events, to bound the work done on
+    /// untrusted files
+    pub fn max_events(mut self, max_events: usize) -> Self {
+        self.options.max_events = Some(max_events);
+        self
+    }
+
+    /// Stops decoding at the first event `max_duration_us` microseconds or
+    /// more after the first event of the file
+    pub fn max_duration_us(mut self, max_duration_us: u64) -> Self {
+        self.options.max_duration_us = Some(max_duration_us);
+        self
+    }
+
+    /// Decodes the file from a memory map, see [`RawFileReader::new_mmap`]
+    #[cfg(feature = "mmap")]
+    pub fn mmap(mut self, mmap: bool) -> Self {
+        self.options.mmap = mmap;
+        self
+    }
+
+    pub fn build(self) -> Result<RawFileReader, RawFileReaderError> {
+        let path = self.path.as_path();
+        let file =
+            File::open(path).map_err(|e| RawFileReaderError::FileOpenError(path.into(), e))?;
+
+        let mut reader = BufReader::with_capacity(64 * 1024, file);
+
+        let mut header = parse_header(&mut reader)?;
+        self.options.apply_overrides(&mut header);
+        self.options.validate_header(&header)?;
+        let data_start = header.header_len;
+        let data_size = reader
+            .get_ref()
+            .metadata()
+            // A declared header size may go past the end of a truncated file
+            .map(|metadata| metadata.len().saturating_sub(data_start))
+            .map_err(|_e| RawFileReaderError::ReadBytesFailed)?;
+
+        let event_iterator = self.options.file_events(reader, &header)?;
+
+        Ok(RawFileReader {
+            path: Some(path.into()),
+            options: self.options,
+            data_size,
+            data_start,
+            event_iterator,
+            stream_start: data_start,
+            time_index: Vec::new(),
+            header,
+            closed: false,
+        })
     }
+}
 
-    /// Resets the file reader
-    pub fn reset(&mut self) {
-        let decoder = Self::new(&self.path).unwrap();
-        *self = decoder;
+impl IntoIterator for RawFileReader {
+    type Item = Event;
+    type IntoIter = DynamicEvtReader;
+
+    /// Consumes the reader, yielding its remaining events
+    fn into_iter(self) -> Self::IntoIter {
+        self.event_iterator
     }
 }
 
 /// Slice configuration options
+#[derive(Debug, Clone)]
 pub enum SliceBy {
     /// Slice by time in microseconds
     Time(u64),
@@ -342,7 +1822,65 @@ pub enum SliceBy {
     Both(u64, usize),
 }
 
-pub fn slice_events<I>(events: I, slice_by: SliceBy) -> impl Iterator<Item = Vec<Event>>
+/// Events of a slice with their time bounds, yielded by [`slice_events`].
+///
+/// It derefs to the events so it can be used as a slice of events.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EventSlice {
+    pub events: Vec<Event>,
+    /// Timestamp of the first event of the slice
+    pub start_us: u64,
+    /// Timestamp after the last event of the slice, exclusive
+    pub end_us: u64,
+}
+
+impl EventSlice {
+    pub fn into_vec(self) -> Vec<Event> {
+        self.events
+    }
+
+    /// Duration between the bounds of the slice in microseconds
+    pub fn duration_us(&self) -> u64 {
+        self.end_us - self.start_us
+    }
+}
+
+impl From<Vec<Event>> for EventSlice {
+    /// Computes the bounds of `events`, both are 0 without timestamped events
+    fn from(events: Vec<Event>) -> Self {
+        let mut timestamps = events.iter().filter_map(Event::timestamp);
+        let (start_us, end_us) = match timestamps.next() {
+            Some(first) => timestamps.fold((first, first + 1), |(start, end), t| {
+                (start.min(t), end.max(t + 1))
+            }),
+            None => (0, 0),
+        };
+        EventSlice {
+            events,
+            start_us,
+            end_us,
+        }
+    }
+}
+
+impl std::ops::Deref for EventSlice {
+    type Target = [Event];
+
+    fn deref(&self) -> &[Event] {
+        &self.events
+    }
+}
+
+impl IntoIterator for EventSlice {
+    type Item = Event;
+    type IntoIter = std::vec::IntoIter<Event>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.events.into_iter()
+    }
+}
+
+pub fn slice_events<I>(events: I, slice_by: SliceBy) -> impl Iterator<Item = EventSlice>
 where
     I: Iterator<Item = Event>,
 {
@@ -356,56 +1894,132 @@ where
     };
 
     std::iter::from_fn(move || {
-        // Find the first event that has a timestamp
-        let first = loop {
-            match iter.next() {
-                Some(e) if e.timestamp().is_some() => break e,
-                Some(_) => continue,
-                None => return None,
-            }
-        };
+        let mut slice = Vec::with_capacity(estimated_capacity);
+        fill_slice(&mut iter, &slice_by, &mut slice).then(|| EventSlice::from(slice))
+    })
+}
 
-        let first_ts = first.timestamp().unwrap();
+/// Same as [`slice_events`] but each slice comes with its index, to display
+/// something like "slice 42 [1.20s-1.22s]" from the bounds of the slice
+pub fn enumerate_slices<I>(
+    events: I,
+    slice_by: SliceBy,
+) -> impl Iterator<Item = (usize, EventSlice)>
+where
+    I: Iterator<Item = Event>,
+{
+    slice_events(events, slice_by).enumerate()
+}
 
-        let (slice_end_time, max_count) = match slice_by {
-            SliceBy::Time(micros) => (Some(first_ts + micros), None),
-            SliceBy::Count(count) => (None, Some(count)),
-            SliceBy::Both(micros, count) => (Some(first_ts + micros), Some(count)),
-        };
+/// Same as [`slice_events`] but every slice is written in the same buffer
+/// instead of a new `Vec`, see [`ReusedSlices::next_slice`]
+pub fn slice_events_reuse<I>(events: I, slice_by: SliceBy) -> ReusedSlices<I>
+where
+    I: Iterator<Item = Event>,
+{
+    ReusedSlices {
+        events: events.peekable(),
+        slice_by,
+        buffer: Vec::new(),
+    }
+}
 
-        let mut slice = Vec::with_capacity(estimated_capacity);
-        slice.push(first);
-
-        if slice_end_time.is_none() {
-            let count = max_count.unwrap();
-            slice.extend(iter.by_ref().take(count - 1));
-            return Some(slice);
-        }
-
-        let end_time = slice_end_time.unwrap();
-        if let Some(count) = max_count {
-            slice.extend(
-                iter.by_ref()
-                    .filter(|e| e.timestamp().is_some())
-                    .take_while(|e| e.timestamp().unwrap() < end_time)
-                    .take(count - 1),
-            );
-        } else {
-            slice.extend(
-                iter.by_ref()
-                    .filter(|e| e.timestamp().is_some())
-                    .take_while(|e| e.timestamp().unwrap() < end_time),
-            );
+/// Slices of events sharing a single buffer, created by [`slice_events_reuse`]
+pub struct ReusedSlices<I: Iterator<Item = Event>> {
+    events: Peekable<I>,
+    slice_by: SliceBy,
+    buffer: Vec<Event>,
+}
+
+impl<I: Iterator<Item = Event>> ReusedSlices<I> {
+    /// Returns the next slice, or `None` at the end of the stream.
+    ///
+    /// The slice borrows the internal buffer, which is overwritten by the
+    /// next call: it must be consumed, or copied, before asking for the next
+    /// one. This is why this isn't an [`Iterator`].
+    pub fn next_slice(&mut self) -> Option<&[Event]> {
+        self.buffer.clear();
+        fill_slice(&mut self.events, &self.slice_by, &mut self.buffer)
+            .then_some(self.buffer.as_slice())
+    }
+}
+
+/// Appends the next slice of `iter` to `slice`, returns false when the
+/// stream has no more timestamped events
+fn fill_slice<I>(iter: &mut Peekable<I>, slice_by: &SliceBy, slice: &mut Vec<Event>) -> bool
+where
+    I: Iterator<Item = Event>,
+{
+    // Find the first event that has a timestamp
+    let first = loop {
+        match iter.next() {
+            Some(e) if e.timestamp().is_some() => break e,
+            Some(_) => continue,
+            None => return false,
         }
+    };
 
-        Some(slice)
-    })
+    let first_ts = first.timestamp().unwrap();
+
+    let (slice_end_time, max_count) = match *slice_by {
+        SliceBy::Time(micros) => (Some(first_ts + micros), None),
+        SliceBy::Count(count) => (None, Some(count)),
+        SliceBy::Both(micros, count) => (Some(first_ts + micros), Some(count)),
+    };
+
+    slice.push(first);
+
+    if slice_end_time.is_none() {
+        let count = max_count.unwrap();
+        slice.extend(iter.by_ref().take(count - 1));
+        return true;
+    }
+
+    // The first event past the end of the slice is left in the stream, it
+    // starts the next slice
+    let end_time = slice_end_time.unwrap();
+    let max_count = max_count.unwrap_or(usize::MAX);
+    while slice.len() < max_count {
+        let Some(event) = iter.next_if(|e| e.timestamp().is_none_or(|t| t < end_time)) else {
+            break;
+        };
+        if event.timestamp().is_some() {
+            slice.push(event);
+        }
+    }
+
+    true
 }
 
 // Python bindings
 #[pyclass]
 pub struct EventIterator {
-    inner: Box<dyn Iterator<Item = Event> + Send + Sync>,
+    inner: DynamicEvtReader,
+    // Adaptors chained from Python, behind a mutex as the stages aren't Sync
+    pipeline: Mutex<EventPipeline>,
+    geometry: CameraGeometry,
+    emitted: u64,
+    estimate: u64,
+}
+
+impl EventIterator {
+    /// Moves the events left to a new iterator with `add_stage` applied to
+    /// its pipeline, this one yields no more events
+    fn chain(&mut self, add_stage: impl FnOnce(EventPipeline) -> EventPipeline) -> EventIterator {
+        let empty =
+            DynamicEvtReader::Evt2(EvtReader::new(Box::new(io::empty()), Evt2Decoder::new()));
+        let pipeline = self
+            .pipeline
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        EventIterator {
+            inner: std::mem::replace(&mut self.inner, empty),
+            pipeline: Mutex::new(add_stage(std::mem::take(pipeline))),
+            geometry: self.geometry,
+            emitted: self.emitted,
+            estimate: self.estimate,
+        }
+    }
 }
 
 #[pymethods]
@@ -415,23 +2029,220 @@ impl EventIterator {
     }
 
     fn __next__(mut slf: PyRefMut<'_, Self>) -> Option<Event> {
-        slf.inner.next()
+        let slf = &mut *slf;
+        let pipeline = slf
+            .pipeline
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        let event = slf
+            .inner
+            .by_ref()
+            .find_map(|event| pipeline.process(event))?;
+        slf.emitted += 1;
+        Some(event)
+    }
+
+    /// Returns an iterator keeping the CD events inside the `[x0, x1) x [y0, y1)`
+    /// region of interest, see [`crop`]. The events left are moved to the
+    /// new iterator, so this one must not be used afterwards.
+    #[pyo3(signature = (x0, y0, x1, y1, rebase=false))]
+    fn crop(&mut self, x0: u16, y0: u16, x1: u16, y1: u16, rebase: bool) -> EventIterator {
+        self.chain(|pipeline| pipeline.crop(x0, y0, x1, y1, rebase))
+    }
+
+    /// Returns an iterator dropping the CD events whose pixel fired less than
+    /// `refractory_us` microseconds before, see [`refractory_filter`]
+    fn refractory(&mut self, refractory_us: u64) -> EventIterator {
+        let CameraGeometry { width, height } = self.geometry;
+        self.chain(|pipeline| pipeline.refractory(width as usize, height as usize, refractory_us))
+    }
+
+    /// Returns an iterator keeping one CD event out of `keep_every`, see [`subsample`]
+    fn subsample(&mut self, keep_every: usize) -> PyResult<EventIterator> {
+        if keep_every == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(
+                "keep_every must be non-zero",
+            ));
+        }
+        Ok(self.chain(|pipeline| pipeline.subsample(keep_every)))
+    }
+
+    /// Estimated number of events of the file, so that progress bars like
+    /// tqdm can show a total
+    fn __len__(&self) -> usize {
+        self.estimate as usize
+    }
+
+    /// Number of events returned so far
+    #[getter]
+    fn emitted(&self) -> u64 {
+        self.emitted
+    }
+
+    /// Fraction of the estimated event count returned so far, capped to 1
+    fn progress(&self) -> f64 {
+        if self.estimate == 0 {
+            return 1.0;
+        }
+        (self.emitted as f64 / self.estimate as f64).min(1.0)
     }
 }
 
-#[pymodule]
-fn libreeb(m: &Bound<'_, PyModule>) -> PyResult<()> {
-    m.add_class::<Event>()?;
-    m.add_class::<EventIterator>()?;
-    m.add_class::<RawFileReader>()?;
-    Ok(())
+#[pyclass]
+pub struct FrameIterator {
+    inner: Box<dyn Iterator<Item = Frame> + Send + Sync>,
 }
 
-// Test module
-#[cfg(test)]
+#[pymethods]
+impl FrameIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(mut slf: PyRefMut<'py, Self>) -> PyResult<Option<Bound<'py, PyArray2<i32>>>> {
+        let Some(frame) = slf.inner.next() else {
+            return Ok(None);
+        };
+        let array = PyArray1::from_vec(slf.py(), frame.data);
+        Ok(Some(array.reshape([frame.height, frame.width])?))
+    }
+}
+
+/// Python exception of an error met when reopening the file of a reader
+fn reopen_error(e: RawFileReaderError) -> PyErr {
+    match e {
+        RawFileReaderError::SourceNotReopenable | RawFileReaderError::FileOpenError(..) => {
+            PyErr::new::<pyo3::exceptions::PyIOError, _>(format!("{}", e))
+        }
+        _ => PyErr::new::<pyo3::exceptions::PyRuntimeError, _>(format!("{}", e)),
+    }
+}
+
+/// Runs `f` with a progress callback calling the Python `callback`, if any.
+/// The first exception raised by `callback` stops the calls and is returned
+/// once `f` is done.
+fn with_py_progress<T>(
+    callback: Option<&Bound<'_, PyAny>>,
+    f: impl FnOnce(Option<&mut dyn FnMut(u64, u64)>) -> T,
+) -> PyResult<T> {
+    let Some(callback) = callback else {
+        return Ok(f(None));
+    };
+    let mut error = None;
+    let mut progress = |done: u64, total: u64| {
+        if error.is_none() {
+            error = callback.call1((done, total)).err();
+        }
+    };
+    let result = f(Some(&mut progress));
+    match error {
+        Some(error) => Err(error),
+        None => Ok(result),
+    }
+}
+
+// Dtype of the structured arrays of CD events, same as Metavision's EventCD
+const CD_EVENT_DTYPE: [(&str, &str); 4] = [("x", "u2"), ("y", "u2"), ("p", "i2"), ("t", "i8")];
+
+/// Converts the CD events of `events` into a numpy structured array
+fn cd_events_to_numpy<'py>(py: Python<'py>, events: &[Event]) -> PyResult<Bound<'py, PyAny>> {
+    let count = events.iter().filter(|e| e.is_cd()).count();
+    let (mut xs, mut ys, mut ps, mut ts) = (
+        Vec::with_capacity(count),
+        Vec::with_capacity(count),
+        Vec::with_capacity(count),
+        Vec::with_capacity(count),
+    );
+    for event in events {
+        if let Event::CD { x, y, p, t } = *event {
+            xs.push(x);
+            ys.push(y);
+            ps.push(p as i16);
+            ts.push(t as i64);
+        }
+    }
+
+    let array = py
+        .import("numpy")?
+        .call_method1("empty", (count, CD_EVENT_DTYPE.to_vec()))?;
+    array.set_item("x", PyArray1::from_vec(py, xs))?;
+    array.set_item("y", PyArray1::from_vec(py, ys))?;
+    array.set_item("p", PyArray1::from_vec(py, ps))?;
+    array.set_item("t", PyArray1::from_vec(py, ts))?;
+    Ok(array)
+}
+
+enum BatchSize {
+    Events(usize),
+    Time(u64),
+}
+
+/// Iterator over the CD events of a file by batches, each batch being a numpy
+/// structured array. Only one batch is held in memory at a time.
+#[pyclass]
+pub struct BatchIterator {
+    inner: DynamicEvtReader,
+    batch_size: BatchSize,
+    pending: Option<Event>, // First event of the next time batch
+    buffer: Vec<Event>,
+}
+
+#[pymethods]
+impl BatchIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__<'py>(mut slf: PyRefMut<'py, Self>) -> PyResult<Option<Bound<'py, PyAny>>> {
+        let this = &mut *slf;
+        this.buffer.clear();
+        let mut cd_events = this
+            .pending
+            .take()
+            .into_iter()
+            .chain(this.inner.by_ref().filter(Event::is_cd));
+        match this.batch_size {
+            BatchSize::Events(n_events) => this.buffer.extend(cd_events.take(n_events)),
+            BatchSize::Time(delta_t_us) => {
+                if let Some(first) = cd_events.next() {
+                    let end = first.timestamp().unwrap_or_default() + delta_t_us;
+                    this.buffer.push(first);
+                    for event in cd_events {
+                        if event.timestamp().unwrap_or_default() >= end {
+                            this.pending = Some(event);
+                            break;
+                        }
+                        this.buffer.push(event);
+                    }
+                }
+            }
+        }
+
+        if this.buffer.is_empty() {
+            return Ok(None);
+        }
+        cd_events_to_numpy(slf.py(), &slf.buffer).map(Some)
+    }
+}
+
+#[pymodule]
+fn libreeb(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<BatchIterator>()?;
+    m.add_class::<Event>()?;
+    m.add_class::<EventIterator>()?;
+    m.add_class::<EventStats>()?;
+    m.add_class::<EventTypeCounts>()?;
+    m.add_class::<FrameIterator>()?;
+    m.add_class::<RawFileReader>()?;
+    m.add_function(wrap_pyfunction!(py_read_header, m)?)?;
+    Ok(())
+}
+
+// Test module
+#[cfg(test)]
 mod tests {
     use super::*;
-    use std::hash::Hasher;
+    use std::{collections::HashSet, hash::Hasher};
     use xxhash_rust::xxh64::Xxh64;
     fn compute_hash<I>(events: I) -> u64
     where
@@ -476,4 +2287,1322 @@ mod tests {
         let hash = compute_hash(event_iterator);
         assert_eq!(hash, 0x7c15d19ed15258fc);
     }
+
+    #[test]
+    fn test_dynamic_reader() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<DynamicEvtReader>();
+
+        for (path, hash) in [
+            ("data/openeb/blinking_leds.raw", 0x7c15d19ed15258fc),
+            ("data/openeb/claque_doigt_evt21.raw", 0x1bf31f5b25480a8a),
+            ("data/openeb/gen4_evt3_hand.raw", 0xeb46994708e41cb9),
+        ] {
+            let reader = RawFileReader::new(Path::new(path)).expect("Failed to open test file");
+            let events = reader.into_iter();
+            assert!(matches!(
+                (&events, path),
+                (DynamicEvtReader::Evt2(_), "data/openeb/blinking_leds.raw")
+                    | (
+                        DynamicEvtReader::Evt21(_),
+                        "data/openeb/claque_doigt_evt21.raw"
+                    )
+                    | (DynamicEvtReader::Evt3(_), "data/openeb/gen4_evt3_hand.raw")
+            ));
+            assert_eq!(compute_hash(events), hash);
+        }
+    }
+
+    #[test]
+    fn test_from_bufread() {
+        let bytes =
+            std::fs::read("data/openeb/gen4_evt3_hand.raw").expect("Failed to read test file");
+        let mut reader = RawFileReader::from_bufread(io::Cursor::new(bytes))
+            .expect("Failed to parse the stream");
+        assert!(matches!(reader.header.event_type, RawEventType::Evt3));
+        let hash = compute_hash(reader.read_events());
+        assert_eq!(hash, 0xeb46994708e41cb9);
+        assert!(matches!(
+            reader.reset(),
+            Err(RawFileReaderError::SourceNotReopenable)
+        ));
+    }
+
+    #[test]
+    fn test_reader_moved_to_thread() {
+        let path = Path::new("data/openeb/claque_doigt_evt21.raw");
+        let expected: Vec<Event> = RawFileReader::new(path)
+            .expect("Failed to open test file")
+            .read_events()
+            .collect();
+
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let head = reader.head(100, None);
+        let rest = std::thread::spawn(move || reader.read_events().collect::<Vec<_>>())
+            .join()
+            .unwrap();
+        assert_eq!([head, rest].concat(), expected);
+    }
+
+    #[test]
+    fn test_decode_error_count() {
+        let bytes =
+            std::fs::read("data/openeb/gen4_evt3_hand.raw").expect("Failed to read test file");
+        let mut reader = RawFileReader::from_bufread(io::Cursor::new(bytes.clone()))
+            .expect("Failed to parse the stream");
+        let expected: Vec<Event> = reader.read_events().collect();
+        let baseline = reader.decode_error_count();
+
+        // Inject words of the unused EVT3 type 0x7 between the data words
+        let mut header_size = 0;
+        while bytes[header_size] == b'%' {
+            header_size += bytes[header_size..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .unwrap()
+                + 1;
+        }
+        let mut corrupted = bytes[..header_size].to_vec();
+        let mut bad_words = 0;
+        for (index, word) in bytes[header_size..].chunks(2).enumerate() {
+            if index % 1000 == 0 {
+                corrupted.extend(0x7123u16.to_le_bytes());
+                bad_words += 1;
+            }
+            corrupted.extend(word);
+        }
+
+        let mut reader = RawFileReader::from_bufread(io::Cursor::new(corrupted))
+            .expect("Failed to parse the stream");
+        let events: Vec<Event> = reader.read_events().collect();
+        assert_eq!(events, expected);
+        assert_eq!(reader.decode_error_count(), baseline + bad_words);
+    }
+
+    #[test]
+    fn test_missing_time_base() {
+        let header = b"% evt 2.0\n% geometry 640x480\n";
+        let cd_on = |x: u32| ((1u32 << 28) | (x << 11)).to_le_bytes();
+
+        let mut truncated = header.to_vec();
+        (0..100).for_each(|x| truncated.extend(cd_on(x)));
+        let mut reader = RawFileReader::from_bufread(io::Cursor::new(truncated))
+            .expect("Failed to parse the stream");
+        assert_eq!(reader.read_events().count(), 0);
+        assert!(matches!(
+            reader.check_time_base(),
+            Err(RawFileReaderError::NoTimeBaseFound)
+        ));
+
+        let mut complete = header.to_vec();
+        complete.extend((0x8u32 << 28).to_le_bytes());
+        (0..100).for_each(|x| complete.extend(cd_on(x)));
+        let mut reader = RawFileReader::from_bufread(io::Cursor::new(complete))
+            .expect("Failed to parse the stream");
+        assert_eq!(reader.read_events().count(), 100);
+        assert!(reader.check_time_base().is_ok());
+
+        // Without any event data there's nothing to complain about
+        let mut reader = RawFileReader::from_bufread(io::Cursor::new(header.to_vec()))
+            .expect("Failed to parse the stream");
+        assert_eq!(reader.read_events().count(), 0);
+        assert!(reader.check_time_base().is_ok());
+    }
+
+    #[test]
+    fn test_raw_words() {
+        let mut reader = RawFileReader::new(Path::new("data/openeb/gen4_evt3_hand.raw"))
+            .expect("Failed to open test file");
+        let words: Vec<(u64, u8)> = reader.raw_words().take(16).collect();
+        assert_eq!(words.len(), 16);
+        // The stream starts with an EVT3 time high word
+        assert_eq!(words[0].1, 0b1000);
+        assert!(words
+            .iter()
+            .all(|&(raw, event_type)| raw >> 12 == event_type as u64));
+    }
+
+    #[test]
+    fn test_reopen_changed_file() {
+        let path = std::env::temp_dir().join(format!("libreeb_reopen_{}.raw", std::process::id()));
+        let mut evt3 = b"% evt 3.0\n% geometry 640x480\n".to_vec();
+        evt3.extend([0x00, 0x80, 0x00, 0x20]);
+        std::fs::write(&path, &evt3).unwrap();
+
+        let reader = RawFileReader::new(&path).expect("Failed to open test file");
+        assert!(reader.reopen_events().is_ok());
+
+        std::fs::write(&path, b"% evt 2.0\n% geometry 640x480\n\0\0\0\x80").unwrap();
+        let result = reader.reopen_events();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(RawFileReaderError::HeaderChanged(_))));
+    }
+
+    #[test]
+    fn test_strict_header() {
+        let path = std::env::temp_dir().join(format!("libreeb_strict_{}.raw", std::process::id()));
+        std::fs::write(&path, b"% evt 3.0\n% endianness little\n\0\0\0\x80").unwrap();
+        let lenient = RawFileReader::new(&path);
+        let strict = RawFileReader::builder(&path).strict_header(true).build();
+        let forced = RawFileReader::builder(&path)
+            .strict_header(true)
+            .geometry(640, 480)
+            .build();
+        std::fs::write(&path, b"% evt 3.0\n% geometry 640x480\n\0\0\0\x80").unwrap();
+        let no_endianness = RawFileReader::builder(&path).strict_header(true).build();
+        std::fs::write(&path, b"% evt 3.0\n\0\0\0\x80").unwrap();
+        let empty = RawFileReader::builder(&path).strict_header(true).build();
+        // Keys are matched regardless of their casing
+        std::fs::write(
+            &path,
+            b"% EVT 3.0\n% Geometry 640x480\n% Endianness little\n\0\0\0\x80",
+        )
+        .unwrap();
+        let mixed_case = RawFileReader::builder(&path).strict_header(true).build();
+        std::fs::remove_file(&path).unwrap();
+
+        let lenient = lenient.expect("Failed to open test file");
+        assert_eq!(
+            lenient.header.camera_geometry,
+            CameraGeometry {
+                width: 0,
+                height: 0
+            }
+        );
+        let missing = |geometry, endianness| MissingHeaderFields {
+            geometry,
+            endianness,
+        };
+        assert!(matches!(
+            strict,
+            Err(RawFileReaderError::IncompleteHeader(fields)) if fields == missing(true, false)
+        ));
+        assert!(forced.is_ok());
+        assert!(matches!(
+            no_endianness,
+            Err(RawFileReaderError::IncompleteHeader(fields)) if fields == missing(false, true)
+        ));
+        let error = empty.err().expect("An empty header isn't strict");
+        assert_eq!(
+            error.to_string(),
+            "The header doesn't give the geometry and endianness of the file"
+        );
+        let mixed_case = mixed_case.expect("Failed to open test file");
+        assert!(mixed_case.header.endianness_declared);
+    }
+
+    #[test]
+    fn test_slice_events_reuse() {
+        let events: Vec<Event> = (0..1000)
+            .map(|i| match i % 7 {
+                0 => Event::Unknown(),
+                _ => Event::cd(0, 0, 1, i * 3),
+            })
+            .collect();
+
+        for slice_by in [
+            SliceBy::Time(100),
+            SliceBy::Count(64),
+            SliceBy::Both(100, 20),
+        ] {
+            let expected: Vec<Vec<Event>> = slice_events(events.iter().copied(), slice_by.clone())
+                .map(EventSlice::into_vec)
+                .collect();
+            let mut slices = slice_events_reuse(events.iter().copied(), slice_by);
+            let mut reused = Vec::new();
+            while let Some(slice) = slices.next_slice() {
+                reused.push(slice.to_vec());
+            }
+            assert_eq!(reused, expected);
+        }
+    }
+
+    #[test]
+    fn test_slice_bounds() {
+        let events: Vec<Event> = (0..1000)
+            .map(|i| match i % 7 {
+                0 => Event::trigger(0, 1, i * 3),
+                _ => Event::cd(0, 0, 1, i * 3 + i % 5),
+            })
+            .collect();
+
+        for slice_by in [SliceBy::Time(100), SliceBy::Count(64)] {
+            for slice in slice_events(events.iter().copied(), slice_by) {
+                let timestamps = slice.iter().filter_map(Event::timestamp);
+                assert_eq!(slice.start_us, timestamps.clone().min().unwrap());
+                assert_eq!(slice.end_us, timestamps.max().unwrap() + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_decoder_word_size() {
+        assert_eq!(Evt2Decoder::word_size(), 4);
+        assert_eq!(Evt21Decoder::word_size(), 8);
+        assert_eq!(Evt3Decoder::word_size(), 2);
+        assert_eq!(DatDecoder::word_size(), 8);
+        assert_eq!(Evt21Decoder::format_name(), "EVT2.1");
+        assert_eq!(RawEventType::Evt3.word_size(), Evt3Decoder::word_size());
+    }
+
+    #[test]
+    fn test_for_each_event() {
+        for file in [
+            "data/openeb/gen4_evt3_hand.raw",
+            "data/openeb/claque_doigt_evt21.raw",
+            "data/openeb/blinking_leds.raw",
+        ] {
+            let path = Path::new(file);
+            let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+            let expected: Vec<Event> = reader.read_events().collect();
+
+            reader.reset().unwrap();
+            let mut events = Vec::new();
+            reader.for_each_event(|event| events.push(event));
+            assert_eq!(events, expected);
+        }
+    }
+
+    #[test]
+    fn test_cd_polarity() {
+        for file in [
+            "data/openeb/gen4_evt3_hand.raw",
+            "data/openeb/claque_doigt_evt21.raw",
+            "data/openeb/blinking_leds.raw",
+        ] {
+            let mut reader = RawFileReader::new(Path::new(file)).expect("Failed to open test file");
+            let polarities: HashSet<u8> = reader
+                .read_events()
+                .filter_map(|e| match e {
+                    Event::CD { p, .. } => Some(p),
+                    _ => None,
+                })
+                .collect();
+            assert!(polarities.iter().all(|&p| p <= 1), "{file}: {polarities:?}");
+        }
+
+        // The unused bits of DAT records must not leak into the polarity
+        let mut events = VecDeque::new();
+        let record = <Dat as zerocopy::FromBytes>::read_from_bytes(&[0xFF; 8]).unwrap();
+        DatDecoder::new().decode(&[record], &mut events);
+        assert!(matches!(events[0], Event::CD { p: 1, .. }));
+    }
+
+    #[test]
+    fn test_read_events_between() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let t_first = reader.read_events().find_map(|e| e.timestamp()).unwrap();
+
+        reader.reset().unwrap();
+        let (start, end) = (t_first + 5_000, t_first + 15_000);
+        let window: Vec<Event> = reader.read_events_between(start, end).unwrap().collect();
+        assert!(!window.is_empty());
+        assert!(window
+            .iter()
+            .all(|e| e.timestamp().is_none_or(|t| (start..end).contains(&t))));
+
+        // Same window when jumping to a checkpoint of the time index
+        reader.reset().unwrap();
+        reader.build_time_index(100).unwrap();
+        let indexed: Vec<Event> = reader.read_events_between(start, end).unwrap().collect();
+        assert_eq!(indexed, window);
+
+        reader.reset().unwrap();
+        assert_eq!(reader.read_events_between(end, start).unwrap().count(), 0);
+        reader.reset().unwrap();
+        assert_eq!(
+            reader
+                .read_events_between(u64::MAX - 1, u64::MAX)
+                .unwrap()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_enumerate_slices() {
+        let events = (0..1000).map(|i| match i % 50 {
+            0 => Event::trigger(0, 1, i * 10),
+            _ => Event::cd(0, 0, 1, i * 10),
+        });
+
+        let slices: Vec<(usize, EventSlice)> =
+            enumerate_slices(events, SliceBy::Time(1000)).collect();
+        assert!(slices.len() > 1);
+        for (i, (index, slice)) in slices.iter().enumerate() {
+            assert_eq!(*index, i);
+            assert!(slice.start_us < slice.end_us);
+            assert!(slice.end_us <= slice.start_us + 1000);
+            assert!(slice
+                .events
+                .iter()
+                .filter_map(Event::timestamp)
+                .all(|t| (slice.start_us..slice.end_us).contains(&t)));
+        }
+    }
+
+    #[test]
+    fn test_slice_triggers() {
+        let events = vec![
+            Event::cd(0, 0, 1, 100),
+            Event::trigger(0, 1, 500),
+            Event::cd(1, 0, 1, 900),
+            Event::trigger(1, 0, 1_100),
+            Event::trigger(0, 0, 2_500),
+            Event::cd(2, 0, 1, 2_600),
+        ];
+
+        let slices: Vec<EventSlice> =
+            slice_events(events.clone().into_iter(), SliceBy::Time(1_000)).collect();
+        assert_eq!(slices.len(), 3);
+        assert_eq!(slices[0].events, events[..3]);
+        // The trigger past the first slice starts the next one instead of being dropped
+        assert_eq!(slices[1].events, [Event::trigger(1, 0, 1_100)]);
+        assert_eq!((slices[1].start_us, slices[1].end_us), (1_100, 1_101));
+        assert_eq!(slices[2].events, events[4..]);
+        assert_eq!(slices[2].start_us, 2_500);
+
+        let slices: Vec<EventSlice> =
+            slice_events(events.into_iter(), SliceBy::Both(1_000, 2)).collect();
+        assert_eq!(slices.len(), 3);
+        assert_eq!(
+            slices[1].events,
+            [Event::cd(1, 0, 1, 900), Event::trigger(1, 0, 1_100)]
+        );
+    }
+
+    #[test]
+    fn test_head() {
+        use std::sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        };
+
+        // Counts the bytes consumed from the inner reader
+        struct CountingReader<R> {
+            inner: R,
+            consumed: Arc<AtomicUsize>,
+        }
+        impl<R: io::Read> io::Read for CountingReader<R> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = self.inner.read(buf)?;
+                self.consumed.fetch_add(n, Ordering::Relaxed);
+                Ok(n)
+            }
+        }
+        impl<R: BufRead> BufRead for CountingReader<R> {
+            fn fill_buf(&mut self) -> io::Result<&[u8]> {
+                self.inner.fill_buf()
+            }
+            fn consume(&mut self, amt: usize) {
+                self.consumed.fetch_add(amt, Ordering::Relaxed);
+                self.inner.consume(amt)
+            }
+        }
+
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let expected: Vec<Event> = RawFileReader::new(path)
+            .expect("Failed to open test file")
+            .read_events()
+            .take(100)
+            .collect();
+
+        let bytes = std::fs::read(path).expect("Failed to read test file");
+        let file_size = bytes.len();
+        let consumed = Arc::new(AtomicUsize::new(0));
+        let mut reader = RawFileReader::from_bufread(CountingReader {
+            inner: io::Cursor::new(bytes),
+            consumed: consumed.clone(),
+        })
+        .expect("Failed to parse the stream");
+        assert_eq!(reader.head(100, None), expected);
+        assert!(consumed.load(Ordering::Relaxed) < file_size / 10);
+    }
+
+    #[test]
+    fn test_progress() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let count = reader.read_events().count() as u64;
+
+        reader.reset().unwrap();
+        let mut calls = Vec::new();
+        let mut progress = |done, total| calls.push((done, total));
+        reader
+            .write_csv(&mut io::sink(), Some(&mut progress))
+            .unwrap();
+        assert!(calls.len() > 1);
+        assert_eq!(calls.last(), Some(&(count, reader.estimate_event_count())));
+
+        reader.reset().unwrap();
+        let mut last = None;
+        let mut progress = |done, _total| last = Some(done);
+        let events = reader.head(1000, Some(&mut progress));
+        assert_eq!(last, Some(events.len() as u64));
+    }
+
+    #[test]
+    fn test_geometry_override() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let header_geometry = RawFileReader::new(path)
+            .expect("Failed to open test file")
+            .header
+            .camera_geometry;
+        assert_ne!(header_geometry.width, 320);
+
+        let mut reader =
+            RawFileReader::new_with_geometry(path, 320, 240).expect("Failed to open test file");
+        let geometry = CameraGeometry {
+            width: 320,
+            height: 240,
+        };
+        assert_eq!(reader.header.camera_geometry, geometry);
+        // The decoder drops the events outside of the forced geometry
+        assert!(reader.read_events().all(|e| e.x().is_none_or(|x| x < 320)));
+
+        reader.reset().unwrap();
+        assert_eq!(reader.header.camera_geometry, geometry);
+    }
+
+    #[test]
+    fn test_reader_builder() {
+        let path = Path::new("data/openeb/blinking_leds.raw");
+        let cd_count = RawFileReader::new(path)
+            .expect("Failed to open test file")
+            .read_events()
+            .filter(Event::is_cd)
+            .count();
+
+        let mut reader = RawFileReader::builder(path)
+            .buffer_size(4096)
+            .cd_only(true)
+            .build()
+            .expect("Failed to open test file");
+        assert_eq!(reader.event_iterator.buffer_size(), 4096);
+        let mut count = 0;
+        assert!(reader
+            .read_events()
+            .inspect(|_| count += 1)
+            .all(|e| e.is_cd()));
+        assert_eq!(count, cd_count);
+    }
+
+    #[test]
+    fn test_big_endian() {
+        let events = vec![
+            Event::cd(3, 4, 1, 10),
+            Event::trigger(1, 1, 70),
+            Event::cd(639, 479, 0, 5_000),
+        ];
+        let mut big = testing::synthetic_evt3(&events);
+        let header_len = parse_header(&mut io::Cursor::new(&big)).unwrap().header_len as usize;
+        evt_reader::swap_word_bytes(&mut big[header_len..], Evt3Decoder::word_size());
+
+        // Declared by the header
+        let mut declared = b"% endianness big\n".to_vec();
+        declared.extend(&big);
+        let mut reader =
+            RawFileReader::from_bufread(io::Cursor::new(declared)).expect("Failed to parse header");
+        assert_eq!(reader.header.endianness, Endianness::Big);
+        assert_eq!(reader.read_events().collect::<Vec<_>>(), events);
+
+        // Forced when opening the file
+        let path = std::env::temp_dir().join(format!("libreeb_big_{}.raw", std::process::id()));
+        std::fs::write(&path, &big).expect("Failed to write test file");
+        let mut reader = RawFileReader::builder(&path)
+            .endianness(Endianness::Big)
+            .buffer_size(4)
+            .build()
+            .expect("Failed to open test file");
+        assert_eq!(reader.read_events().collect::<Vec<_>>(), events);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_polarity_convention() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let raw: Vec<Event> = RawFileReader::new(path)
+            .expect("Failed to open test file")
+            .read_events()
+            .collect();
+        let decode = |convention| {
+            RawFileReader::builder(path)
+                .polarity_convention(convention)
+                .build()
+                .expect("Failed to open test file")
+                .read_events()
+                .collect::<Vec<Event>>()
+        };
+
+        assert_eq!(decode(PolarityConvention::Raw), raw);
+        let inverted = decode(PolarityConvention::ZeroIsPositive);
+        assert_eq!(inverted.len(), raw.len());
+        assert!(raw.iter().any(|e| e.polarity() == Some(0)));
+        for (inverted, raw) in inverted.iter().zip(&raw) {
+            match (inverted, raw) {
+                (Event::CD { p: inverted, .. }, Event::CD { p: raw, .. }) => {
+                    assert_eq!(*inverted, 1 - raw)
+                }
+                _ => assert_eq!(inverted, raw),
+            }
+        }
+    }
+
+    #[test]
+    fn test_time_index() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut indexed = RawFileReader::new(path).expect("Failed to open test file");
+        let index = indexed
+            .build_time_index(16)
+            .expect("Failed to index test file");
+        assert!(index.len() >= 8 && index.len() <= 16);
+        assert!(index
+            .windows(2)
+            .all(|w| w[0].0 <= w[1].0 && w[0].1 < w[1].1));
+        assert!(index[0].1 > indexed.data_start);
+
+        for target in [0, 1_234_567, 2_000_000, 3_900_000, u64::MAX] {
+            let mut naive = RawFileReader::new(path).expect("Failed to open test file");
+            naive.seek_to(target).unwrap();
+            indexed.reset().unwrap();
+            indexed.seek_to(target).unwrap();
+            if target == 2_000_000 {
+                assert!(indexed.stream_start > indexed.data_start);
+            }
+
+            let expected: Vec<Event> = naive.read_events().collect();
+            assert!(expected
+                .first()
+                .is_none_or(|e| e.timestamp().is_none_or(|t| t >= target)));
+            assert_eq!(indexed.read_events().collect::<Vec<Event>>(), expected);
+        }
+
+        // Targets just past a checkpoint, whose events decoded before its
+        // offset must all be counted in its timestamp
+        for &(checkpoint_us, _) in &index {
+            let target = checkpoint_us + 1;
+            let mut naive = RawFileReader::new(path).expect("Failed to open test file");
+            naive.seek_to(target).unwrap();
+            indexed.reset().unwrap();
+            indexed.seek_to(target).unwrap();
+            let expected: Vec<Event> = naive.read_events().take(1000).collect();
+            let events: Vec<Event> = indexed.read_events().take(1000).collect();
+            assert_eq!(events, expected, "seek to {target}");
+        }
+
+        // Seeks are relative to the current position
+        indexed.reset().unwrap();
+        indexed.seek_to(3_000_000).unwrap();
+        indexed.seek_to(1_000_000).unwrap();
+        let t = indexed.read_events().find_map(|e| e.timestamp()).unwrap();
+        assert!(t >= 3_000_000);
+    }
+
+    #[test]
+    fn test_reset_to() {
+        let events: Vec<Event> = (0..30)
+            .map(|k| match k % 4 {
+                3 => Event::trigger(1, 1, k * 150_000 + 7),
+                _ => Event::cd(k as u16, 2, 1, k * 150_000 + 7),
+            })
+            .collect();
+        let path =
+            std::env::temp_dir().join(format!("libreeb_reset_to_{}.raw", std::process::id()));
+        std::fs::write(&path, testing::synthetic_evt3(&events)).expect("Failed to write test file");
+        let segment: Vec<Event> = events
+            .iter()
+            .copied()
+            .filter(|e| (2_000_000..3_000_000).contains(&e.timestamp().unwrap()))
+            .collect();
+        assert_eq!(segment[0], Event::cd(14, 2, 1, 2_100_007));
+
+        // Loops over the segment from 2s to 3s
+        let mut reader = RawFileReader::new(&path).expect("Failed to open test file");
+        for _ in 0..2 {
+            reader.reset_to(2_000_000).unwrap();
+            let looped: Vec<Event> = reader
+                .read_events()
+                .take_while(|e| e.timestamp() < Some(3_000_000))
+                .collect();
+            assert_eq!(looped, segment);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        let mut stdin_like = RawFileReader::from_bufread(io::Cursor::new(Vec::from("% evt 3.0\n")))
+            .expect("Failed to parse the stream");
+        assert!(matches!(
+            stdin_like.reset_to(0),
+            Err(RawFileReaderError::SourceNotReopenable)
+        ));
+    }
+
+    #[test]
+    fn test_reader_save_and_restore_state() {
+        let events: Vec<Event> = (0..20_000u64)
+            .map(|i| Event::cd((i % 64) as u16, (i / 64 % 48) as u16, (i % 2) as u8, i * 3))
+            .collect();
+        let path = std::env::temp_dir().join(format!("libreeb_state_{}.raw", std::process::id()));
+        std::fs::write(&path, testing::synthetic_evt3(&events)).expect("Failed to write test file");
+
+        // Saved after jumping to a checkpoint, so the stream doesn't start
+        // with the event data anymore
+        let mut reader = RawFileReader::new(&path).expect("Failed to open test file");
+        reader.build_time_index(10).unwrap();
+        reader.seek_to(30_000).unwrap();
+        let start = events
+            .iter()
+            .position(|e| e.timestamp() >= Some(30_000))
+            .unwrap();
+        let first: Vec<Event> = reader.read_events().take(1000).collect();
+        assert_eq!(first, events[start..start + 1000]);
+        let state = reader.save_state();
+        #[cfg(feature = "serde")]
+        let state: ReaderState =
+            serde_json::from_str(&serde_json::to_string(&state).unwrap()).unwrap();
+
+        let mut resumed = RawFileReader::new(&path).expect("Failed to open test file");
+        resumed.restore_state(state.clone()).unwrap();
+        assert_eq!(
+            resumed.read_events().collect::<Vec<_>>(),
+            events[start + 1000..]
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let mut other =
+            RawFileReader::from_bufread(io::Cursor::new(testing::synthetic_evt21(&events)))
+                .expect("Failed to parse the stream");
+        assert!(matches!(
+            other.event_iterator.restore_state(state),
+            Err(RawFileReaderError::IncompatibleState)
+        ));
+    }
+
+    #[test]
+    fn test_parse_header_format_parts() {
+        let mut raw: &[u8] = b"% date 2023-01-01 12:00:00\n\
+            % format EVT21;endianness=big;height=240;width=320\n\
+            % geometry 640x480\n\
+            \x00";
+        let header = parse_header(&mut raw).expect("Failed to parse header");
+        assert!(matches!(header.event_type, RawEventType::Evt21));
+        assert_eq!(
+            header.camera_geometry,
+            CameraGeometry {
+                width: 320,
+                height: 240
+            }
+        );
+        assert_eq!(header.endianness, Endianness::Big);
+        assert_eq!(header.header_dict["width"], "320");
+        assert_eq!(header.header_dict["height"], "240");
+        assert_eq!(header.header_dict["endianness"], "big");
+        assert_eq!(raw, b"\x00");
+    }
+
+    #[test]
+    fn test_parse_header_key_casing() {
+        let header = parse_header(&mut io::Cursor::new(
+            "% Format EVT21\n% GEOMETRY  320x240\n% Endianness big\n",
+        ))
+        .expect("Failed to parse header");
+        assert!(matches!(header.event_type, RawEventType::Evt21));
+        assert_eq!(header.camera_geometry.width, 320);
+        assert_eq!(header.endianness, Endianness::Big);
+        // The original casing is kept in the dictionary
+        assert_eq!(header.header_dict["Format"], "EVT21");
+        assert_eq!(header.header_dict["GEOMETRY"], "320x240");
+    }
+
+    #[test]
+    fn test_parse_header_tabs() {
+        let header = parse_header(&mut io::Cursor::new(
+            "%format\tEVT3\n%\tgeometry \t640x480\n",
+        ))
+        .expect("Failed to parse header");
+        assert_eq!(header.event_type, RawEventType::Evt3);
+        assert_eq!(
+            header.camera_geometry,
+            CameraGeometry {
+                width: 640,
+                height: 480
+            }
+        );
+        assert_eq!(header.header_dict["format"], "EVT3");
+    }
+
+    #[test]
+    fn test_data_starting_with_percent() {
+        // A time high word of 0x025, whose first byte is '%', and a CD event
+        let data = [0x25, 0x80, 0x2A, 0x00, 0x07, 0x28];
+        let expected = vec![Event::cd(7, 42, 1, 0x25 << 12)];
+        for header in [
+            "% evt 3.0\n% geometry 640x480\n",
+            "% evt 3.0\n% geometry 640x480\n% end\n",
+        ] {
+            let mut raw = header.as_bytes().to_vec();
+            raw.extend(data);
+            let mut reader =
+                RawFileReader::from_bufread(io::Cursor::new(raw)).expect("Failed to parse header");
+            assert_eq!(reader.header.camera_geometry.width, 640);
+            assert_eq!(reader.read_events().collect::<Vec<Event>>(), expected);
+        }
+
+        // The last header line may have no trailing newline
+        let header = parse_header(&mut io::Cursor::new("% evt 3.0\n% geometry 640x480")).unwrap();
+        assert_eq!(header.camera_geometry.height, 480);
+
+        assert!(is_header_line(b"% date 2023-03-29\r\n\x25\x80"));
+        assert!(is_header_line("% serial é".as_bytes()));
+        assert!(is_header_line(&"% serial é".as_bytes()[..10]));
+        assert!(!is_header_line(&data));
+    }
+
+    #[test]
+    fn test_header_len() {
+        let data = [0x25, 0x80, 0x2A, 0x00, 0x07, 0x28];
+        let header = "% evt 3.0\n% geometry 640x480\n%end\n";
+        let mut raw = header.as_bytes().to_vec();
+        raw.extend(data);
+        let mut reader = io::Cursor::new(raw);
+        let parsed = parse_header(&mut reader).expect("Failed to parse header");
+        assert_eq!(parsed.header_len, header.len() as u64);
+        assert_eq!(reader.position(), parsed.header_len);
+
+        // The declared header size includes padding after the last line, its
+        // key is matched regardless of its casing like the other ones
+        for header in [
+            "% evt 3.0\n% header_size 32\n% end\n",
+            "% evt 3.0\n% Header_Size 32\n% end\n",
+        ] {
+            let mut raw = header.as_bytes().to_vec();
+            raw.resize(32, 0);
+            raw.extend(data);
+            let mut reader =
+                RawFileReader::from_bufread(io::Cursor::new(raw)).expect("Failed to parse header");
+            assert_eq!(reader.header.header_len, 32);
+            assert_eq!(reader.read_events().count(), 1);
+        }
+
+        let result = parse_header(&mut io::Cursor::new("% evt 3.0\n% header_size 4\n"));
+        assert!(matches!(result, Err(RawFileReaderError::ParseHeaderFailed)));
+
+        // A file truncated before the end of its declared header has no events
+        let path =
+            std::env::temp_dir().join(format!("libreeb_truncated_{}.raw", std::process::id()));
+        let mut raw = b"% evt 3.0\n% header_size 4096\n% end\n".to_vec();
+        raw.extend(data);
+        std::fs::write(&path, raw).expect("Failed to write test file");
+        let check = |builder: RawFileReaderBuilder| {
+            let mut reader = builder.build().expect("Failed to open test file");
+            assert_eq!(reader.header.header_len, 4096);
+            assert_eq!(reader.estimate_event_count(), 0);
+            assert_eq!(reader.read_events().count(), 0);
+        };
+        check(RawFileReader::builder(&path));
+        #[cfg(feature = "mmap")]
+        check(RawFileReader::builder(&path).mmap(true));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let result = parse_header(&mut io::Cursor::new(Vec::new()));
+        assert!(matches!(result, Err(RawFileReaderError::HeaderMissing)));
+        let result = parse_header(&mut io::Cursor::new(vec![0x00, 0x80, 0x00, 0x20]));
+        assert!(matches!(result, Err(RawFileReaderError::HeaderMissing)));
+
+        let mut reader =
+            RawFileReader::from_bufread(io::Cursor::new(b"% evt 3.0\n% geometry 640x480\n"))
+                .expect("A header without events is a valid file");
+        assert_eq!(reader.header.event_type, RawEventType::Evt3);
+        assert_eq!(reader.read_events().count(), 0);
+    }
+
+    #[test]
+    fn test_geometry_from_generation() {
+        let header = parse_header(&mut io::Cursor::new("% evt 3.0\n% generation 4.0\n")).unwrap();
+        assert_eq!(
+            header.camera_geometry,
+            CameraGeometry {
+                width: 1280,
+                height: 720
+            }
+        );
+
+        // An explicit geometry always wins
+        let header = parse_header(&mut io::Cursor::new(
+            "% evt 3.0\n% generation 4.0\n% geometry 320x320\n",
+        ))
+        .unwrap();
+        assert_eq!(header.camera_geometry.width, 320);
+
+        let header = parse_header(&mut io::Cursor::new("% evt 3.0\n% generation 9.9\n")).unwrap();
+        assert_eq!(header.camera_geometry.width, 0);
+    }
+
+    #[test]
+    fn test_header_metadata() {
+        let mut raw = io::Cursor::new(
+            "% camera_integrator_name Prophesee\n\
+             % date 2023-03-29 16:37:46\n\
+             % evt 3.0\n\
+             % generation 4.2\n\
+             % geometry 1280x720\n\
+             % serial_number 00ca0009\n\
+             % end\n",
+        );
+        let header = parse_header(&mut raw).expect("Failed to parse header");
+        assert_eq!(header.recording_date(), Some("2023-03-29 16:37:46"));
+        assert_eq!(header.serial_number(), Some("00ca0009"));
+        assert_eq!(header.integrator_name(), Some("Prophesee"));
+        assert_eq!(header.sensor_generation(), Some("4.2"));
+        assert_eq!(header.header_dict["generation"], "4.2");
+
+        let header = parse_header(&mut io::Cursor::new("% evt 3.0\n")).unwrap();
+        assert_eq!(header.recording_date(), None);
+        assert_eq!(header.sensor_generation(), None);
+
+        // The keys are matched regardless of their casing, as when parsing
+        let header = parse_header(&mut io::Cursor::new(
+            "% EVT 3.0\n% Serial_Number 00ca0009\n% Generation 4.1\n",
+        ))
+        .unwrap();
+        assert_eq!(header.serial_number(), Some("00ca0009"));
+        assert_eq!(header.sensor_generation(), Some("4.1"));
+        assert_eq!(header.camera_geometry.width, 1280);
+    }
+
+    #[test]
+    fn test_parse_header_geometry() {
+        let mut raw: &[u8] = b"% evt 3.0\n% geometry 1280x720\n\x00";
+        let header = parse_header(&mut raw).expect("Failed to parse header");
+        assert_eq!(
+            header.camera_geometry,
+            CameraGeometry {
+                width: 1280,
+                height: 720
+            }
+        );
+        assert_eq!(header.endianness, Endianness::Little);
+    }
+
+    #[test]
+    fn test_supported_formats() {
+        let formats = supported_formats();
+        assert_eq!(formats.len(), 4);
+        assert!(formats.contains(&(RawEventType::Evt3, true, true)));
+        assert!(formats.contains(&(RawEventType::Evt4, false, false)));
+        // Every decodable format can be opened
+        for (event_type, decode, _) in formats {
+            let header = RawFileHeader {
+                header_dict: HashMap::new(),
+                event_type: *event_type,
+                camera_geometry: CameraGeometry {
+                    width: 0,
+                    height: 0,
+                },
+                endianness: Endianness::Little,
+                endianness_declared: false,
+                header_len: 0,
+            };
+            let source: ByteSource = Box::new(io::Cursor::new(Vec::new()));
+            let reader = DynamicEvtReader::new(source, &header, &ReaderOptions::default());
+            assert_eq!(reader.is_ok(), *decode);
+        }
+    }
+
+    #[test]
+    fn test_event_ordering() {
+        use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+        let mut events: Vec<Event> = RawFileReader::new(Path::new("data/openeb/blinking_leds.raw"))
+            .expect("Failed to open test file")
+            .read_events()
+            .take(5_000)
+            .chain([Event::Unknown()])
+            .collect();
+        assert!(events.iter().any(Event::is_trigger));
+        let mut shuffled = events.clone();
+        shuffled.shuffle(&mut StdRng::seed_from_u64(7));
+        shuffled.sort();
+
+        assert_eq!(shuffled[0], Event::Unknown());
+        assert!(shuffled
+            .windows(2)
+            .all(|w| w[0].timestamp() <= w[1].timestamp()));
+        events.sort();
+        assert_eq!(shuffled, events);
+
+        // Consistent with equality
+        let cd = Event::cd(1, 2, 1, 100);
+        assert_eq!(cd.cmp(&cd), std::cmp::Ordering::Equal);
+        assert!(cd < Event::cd(1, 2, 0, 101));
+        assert!(cd < Event::trigger(0, 0, 100));
+        assert!(Event::cd(2, 1, 1, 100) < cd);
+    }
+
+    #[test]
+    fn test_event_accessors() {
+        let cd = Event::cd(1, 2, 1, 100);
+        assert!(cd.is_cd());
+        assert!(!cd.is_trigger());
+        assert_eq!(cd.coords(), Some((1, 2)));
+        assert_eq!(cd.as_cd(), Some((1, 2, 1, 100)));
+        assert_eq!(cd.timestamp(), Some(100));
+
+        let trigger = Event::trigger(3, 0, 200);
+        assert!(!trigger.is_cd());
+        assert!(trigger.is_trigger());
+        assert_eq!(trigger.coords(), None);
+        assert_eq!(trigger.as_cd(), None);
+        assert_eq!(trigger.timestamp(), Some(200));
+
+        let unknown = Event::Unknown();
+        assert!(!unknown.is_cd());
+        assert!(!unknown.is_trigger());
+        assert_eq!(unknown.coords(), None);
+        assert_eq!(unknown.as_cd(), None);
+        assert_eq!(unknown.timestamp(), None);
+    }
+
+    #[test]
+    fn test_event_display() {
+        assert_eq!(Event::cd(12, 34, 1, 5678).to_string(), "CD(12,34,1)@5678");
+        assert_eq!(Event::trigger(2, 0, 910).to_string(), "Trigger(2,0)@910");
+        assert_eq!(Event::Unknown().to_string(), "Unknown");
+        assert_eq!(
+            Event::cd(1, 2, 0, 3),
+            Event::CD {
+                x: 1,
+                y: 2,
+                p: 0,
+                t: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_into_iterator() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let expected = reader.read_events().count();
+
+        let reader = RawFileReader::new(path).expect("Failed to open test file");
+        let mut count = 0;
+        for _event in reader {
+            count += 1;
+        }
+        assert_eq!(count, expected);
+    }
+
+    #[test]
+    fn test_next_batch() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let expected: Vec<Event> = reader.read_events().collect();
+
+        let file = File::open(path).expect("Failed to open test file");
+        let mut file_reader = BufReader::new(file);
+        parse_header(&mut file_reader).expect("Failed to parse header");
+        let mut evt_reader = EvtReader::new(file_reader, Evt3Decoder::new());
+        let mut events = Vec::new();
+        while evt_reader.next_batch(&mut events, 1000) > 0 {}
+        assert_eq!(events, expected);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap() {
+        for file in [
+            "data/openeb/gen4_evt3_hand.raw",
+            "data/openeb/claque_doigt_evt21.raw",
+            "data/openeb/blinking_leds.raw",
+        ] {
+            let path = Path::new(file);
+            let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+            let expected = compute_hash(reader.read_events());
+
+            let mut reader = RawFileReader::new_mmap(path).expect("Failed to map test file");
+            assert_eq!(compute_hash(reader.read_events()), expected);
+
+            // The events are decoded from the map again after a reset
+            reader.reset().unwrap();
+            assert_eq!(compute_hash(reader.read_events()), expected);
+        }
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_big_endian_mmap() {
+        let events = vec![
+            Event::cd(3, 4, 1, 10),
+            Event::trigger(1, 1, 70),
+            Event::cd(639, 479, 0, 5_000),
+        ];
+        let mut data = testing::synthetic_evt3(&events);
+        let header_len = parse_header(&mut io::Cursor::new(&data))
+            .unwrap()
+            .header_len as usize;
+        evt_reader::swap_word_bytes(&mut data[header_len..], Evt3Decoder::word_size());
+        let mut big = b"% endianness big\n".to_vec();
+        big.extend(data);
+        let path =
+            std::env::temp_dir().join(format!("libreeb_big_mmap_{}.raw", std::process::id()));
+        std::fs::write(&path, &big).expect("Failed to write test file");
+
+        // The words are swapped the same way whether they are read or mapped
+        let decode = |mmap| {
+            let mut reader = RawFileReader::builder(&path)
+                .mmap(mmap)
+                .build()
+                .expect("Failed to open test file");
+            let decoded: Vec<Event> = reader.read_events().collect();
+            reader.reset().unwrap();
+            let words: Vec<(u64, u8)> = reader.raw_words().collect();
+            (decoded, words)
+        };
+        let (buffered, buffered_words) = decode(false);
+        let (mapped, mapped_words) = decode(true);
+        assert_eq!(buffered, events);
+        assert_eq!(mapped, events);
+        assert_eq!(mapped_words, buffered_words);
+        assert_eq!(buffered_words[0].1, 0x8); // EVT3 time high
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_capped_reader() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let all: Vec<Event> = reader.read_events().collect();
+
+        let mut reader =
+            RawFileReader::new_capped(path, Some(1234), None).expect("Failed to open test file");
+        let events: Vec<Event> = reader.read_events().collect();
+        assert_eq!(events, all[..1234]);
+
+        let first_t = all.iter().find_map(Event::timestamp).unwrap();
+        let end = first_t + 10_000;
+        let expected: Vec<Event> = all
+            .iter()
+            .copied()
+            .take_while(|e| e.timestamp().is_none_or(|t| t < end))
+            .collect();
+        assert!(expected.len() < all.len());
+        let mut reader =
+            RawFileReader::new_capped(path, None, Some(10_000)).expect("Failed to open test file");
+        let mut events = Vec::new();
+        reader.for_each_event(|e| events.push(e));
+        assert_eq!(events, expected);
+
+        // The first limit reached stops the stream
+        let mut reader = RawFileReader::builder(path)
+            .max_events(expected.len() + 10)
+            .max_duration_us(10_000)
+            .build()
+            .expect("Failed to open test file");
+        assert_eq!(reader.read_events().count(), expected.len());
+    }
+
+    #[test]
+    fn test_decode_to_soa() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let expected: Vec<Event> = reader.read_events().filter(Event::is_cd).collect();
+
+        reader.reset().unwrap();
+        let soa = decode_to_soa(reader);
+        assert_eq!(soa.len(), expected.len());
+        assert_eq!(soa.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(soa.get(soa.len() - 1), expected.last().copied());
+        assert_eq!(soa.get(soa.len()), None);
+    }
+
+    #[test]
+    fn test_decode_shared() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let reader = RawFileReader::new(path).expect("Failed to open test file");
+        let expected = event_stats(reader.into_iter());
+
+        let events = decode_shared(RawFileReader::new(path).expect("Failed to open test file"));
+        let consumers: Vec<_> = (0..2)
+            .map(|_| {
+                let events = events.clone();
+                std::thread::spawn(move || event_stats(events.iter()))
+            })
+            .collect();
+        for consumer in consumers {
+            let stats = consumer.join().unwrap();
+            assert_eq!(stats.cd_count, expected.cd_count);
+            assert_eq!(stats.positive_count, expected.positive_count);
+            assert_eq!(stats.min_t, expected.min_t);
+            assert_eq!(stats.max_t, expected.max_t);
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_decode_to_ndarray() {
+        let path = Path::new("data/openeb/claque_doigt_evt21.raw");
+        let soa = decode_to_soa(RawFileReader::new(path).expect("Failed to open test file"));
+        let (x, y, p, t) =
+            decode_to_ndarray(RawFileReader::new(path).expect("Failed to open test file"));
+        assert!(!soa.is_empty());
+        assert_eq!(x.to_vec(), soa.x);
+        assert_eq!(y.to_vec(), soa.y);
+        assert_eq!(p.to_vec(), soa.p);
+        assert_eq!(t.to_vec(), soa.t);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_decode() {
+        for path in [
+            "data/openeb/gen4_evt3_hand.raw",
+            "data/openeb/claque_doigt_evt21.raw",
+            "data/openeb/blinking_leds.raw",
+        ] {
+            let reader = RawFileReader::new(Path::new(path)).expect("Failed to open test file");
+            let parallel = reader.par_decode(None).expect("Failed to decode test file");
+            assert_eq!(parallel, decode_to_soa(reader));
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_par_decode_progress() {
+        use std::ops::ControlFlow;
+
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let reader = RawFileReader::new(path).expect("Failed to open test file");
+        let mut last = None;
+        let mut progress = |done, total| {
+            last = Some((done, total));
+            ControlFlow::Continue(())
+        };
+        let soa = reader
+            .par_decode(Some(&mut progress))
+            .expect("Failed to decode test file");
+        assert_eq!(
+            last,
+            Some((soa.len() as u64, reader.estimate_event_count()))
+        );
+
+        let mut calls = 0;
+        let mut cancel = |_, _| {
+            calls += 1;
+            ControlFlow::Break(())
+        };
+        assert!(matches!(
+            reader.par_decode(Some(&mut cancel)),
+            Err(RawFileReaderError::Cancelled)
+        ));
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn test_read_header() {
+        let path = Path::new("data/openeb/claque_doigt_evt21.raw");
+        let header = read_header(path).expect("Failed to read the header");
+        let reader = RawFileReader::new(path).expect("Failed to open test file");
+        assert_eq!(header.event_type, reader.header.event_type);
+        assert_eq!(header, reader.header);
+    }
+
+    #[test]
+    fn test_estimate_event_count() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let estimate = reader.estimate_event_count() as f64;
+        let count = reader.read_events().count() as f64;
+        assert!(estimate > 0.0);
+        assert!((0.25..4.0).contains(&(estimate / count)));
+    }
+
+    #[test]
+    fn test_event_stats_on_sample() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let (cd, pos, triggers, min_t, max_t) = reader.read_events().fold(
+            (0, 0, 0, u64::MAX, 0),
+            |(cd, pos, triggers, min_t, max_t), e| match e {
+                Event::CD { p, t, .. } => {
+                    (cd + 1, pos + p as u64, triggers, min_t.min(t), max_t.max(t))
+                }
+                Event::ExternalTrigger { .. } => (cd, pos, triggers + 1, min_t, max_t),
+                Event::Unknown() => (cd, pos, triggers, min_t, max_t),
+            },
+        );
+
+        reader.reset().unwrap();
+        let stats = reader.event_stats();
+        assert_eq!(stats.cd_count, cd);
+        assert_eq!(stats.positive_count, pos);
+        assert_eq!(stats.negative_count, cd - pos);
+        assert_eq!(stats.trigger_count, triggers);
+        assert_eq!(stats.duration_us, max_t - min_t);
+    }
+
+    #[test]
+    fn test_count_by_type() {
+        let events = [
+            Event::cd(3, 4, 1, 10),
+            Event::cd(4, 4, 1, 10),
+            Event::cd(5, 4, 0, 12),
+            Event::trigger(2, 1, 70),
+            Event::cd(0, 47, 1, 5_000),
+            Event::cd(7, 7, 0, 20_000),
+        ];
+        let mut data = testing::synthetic_evt21(&events);
+        // An EVT2.1 word of the "others" type, decoded as an unknown event
+        data.extend((0b1110u64 << 60).to_le_bytes());
+        let path = std::env::temp_dir().join(format!("libreeb_counts_{}.raw", std::process::id()));
+        std::fs::write(&path, data).expect("Failed to write test file");
+
+        let mut reader = RawFileReader::builder(&path)
+            .unknown_policy(UnknownPolicy::Emit)
+            .build()
+            .expect("Failed to open test file");
+        let counts = reader.count_by_type();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            [
+                counts.cd_pos,
+                counts.cd_neg,
+                counts.triggers,
+                counts.unknown
+            ],
+            [3, 2, 1, 1]
+        );
+        assert_eq!(counts.total(), 7);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_event_serde_round_trip() {
+        let events = [
+            Event::CD {
+                x: 12,
+                y: 34,
+                p: 1,
+                t: 5678,
+            },
+            Event::ExternalTrigger {
+                id: 2,
+                p: 0,
+                t: 91011,
+            },
+            Event::Unknown(),
+        ];
+        for event in events {
+            let json = serde_json::to_string(&event).unwrap();
+            let decoded: Event = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded, event);
+        }
+        assert_eq!(
+            serde_json::to_string(&events[0]).unwrap(),
+            r#"{"type":"CD","data":{"x":12,"y":34,"p":1,"t":5678}}"#
+        );
+    }
 }
@@ -15,12 +15,46 @@ macro_rules! declare_raw_evt {
                 /// Extracts bits $high:$low from the raw data
                 /// The extraction works by:
                 /// 1. Right-shifting by $low positions to align the field to bit 0
-                /// 2. Masking with ((1 << field_width) - 1) to isolate the field bits
+                /// 2. Masking with the field_width lowest bits set to isolate the field bits,
+                ///    built from the max value so that a full width field doesn't overflow
                 /// 3. Casting to the target return type
                 fn $field(&self) -> $ret_ty {
-                    ((self.data >> $low) & ((1 << ($high - $low + 1)) - 1)) as $ret_ty
+                    let mask = <$data_ty>::MAX >> (<$data_ty>::BITS - ($high - $low + 1));
+                    ((self.data >> $low) & mask) as $ret_ty
                 }
             )+
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use zerocopy::{FromBytes, Immutable, KnownLayout};
+
+    declare_raw_evt! {
+        struct Wide(u64);
+        all(u64): 63, 0;
+        top(u8): 63, 56;
+        low(u32): 31, 0;
+    }
+
+    declare_raw_evt! {
+        struct Narrow(u16);
+        all(u16): 15, 0;
+        bit(u8): 15, 15;
+    }
+
+    #[test]
+    fn test_full_width_field() {
+        let wide = Wide {
+            data: 0xFEDC_BA98_7654_3210,
+        };
+        assert_eq!(wide.all(), 0xFEDC_BA98_7654_3210);
+        assert_eq!(wide.top(), 0xFE);
+        assert_eq!(wide.low(), 0x7654_3210);
+
+        let narrow = Narrow { data: u16::MAX };
+        assert_eq!(narrow.all(), u16::MAX);
+        assert_eq!(narrow.bit(), 1);
+    }
+}
@@ -0,0 +1,174 @@
+use color_eyre::{eyre::bail, Result};
+use libreeb::{
+    event_stats, frame_stream, save_frame_png, supported_formats, write_csv, DatFileReader,
+    EventSource, RawFileReader,
+};
+use std::{
+    fs::File,
+    io::{stdin, stdout, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+const HELP: &str = "\
+Usage: ebtool [COMMAND] <FILE> [OPTIONS]
+
+Commands:
+  formats               List the event formats and whether they can be
+                        decoded and encoded
+  info                  Print the file header and event statistics
+  dump [--limit N]      Print the first N events (all events by default)
+  convert --to csv      Convert the CD events to CSV
+          [--output PATH]   Write to PATH instead of stdout
+  convert --to png-frames --window US
+                        Accumulate the events in windows of US microseconds
+                        and save each frame as a PNG image
+          [--output DIR]    Write the images to DIR instead of the current
+                            directory
+
+Without a command, every event of FILE is printed. FILE is a raw file, or
+a DAT file when its extension is .dat. Use - as FILE to read raw data from
+stdin.
+";
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let mut pargs = pico_args::Arguments::from_env();
+
+    if pargs.contains(["-h", "--help"]) {
+        print!("{}", HELP);
+        return Ok(());
+    }
+
+    let command = pargs.subcommand()?;
+    match command.as_deref() {
+        Some("formats") => {
+            finish(pargs)?;
+            formats()
+        }
+        Some("info") => {
+            let path = pargs.free_from_str()?;
+            finish(pargs)?;
+            info(path)
+        }
+        Some("dump") => {
+            let limit = pargs.opt_value_from_str("--limit")?;
+            let path = pargs.free_from_str()?;
+            finish(pargs)?;
+            dump(path, limit)
+        }
+        Some("convert") => {
+            let to: String = pargs.value_from_str("--to")?;
+            let output = pargs.opt_value_from_str("--output")?;
+            let window = pargs.opt_value_from_str("--window")?;
+            let path = pargs.free_from_str()?;
+            finish(pargs)?;
+            convert(path, &to, output, window)
+        }
+        Some(path) => {
+            let path = PathBuf::from(path);
+            finish(pargs)?;
+            dump(path, None)
+        }
+        None => {
+            print!("{}", HELP);
+            bail!("Missing input file")
+        }
+    }
+}
+
+/// Fails on the arguments left once a command is parsed, like mistyped options
+fn finish(pargs: pico_args::Arguments) -> Result<()> {
+    let remaining = pargs.finish();
+    if !remaining.is_empty() {
+        bail!("Unexpected arguments: {:?}", remaining);
+    }
+    Ok(())
+}
+
+fn open(path: &Path) -> Result<Box<dyn EventSource>> {
+    if path == Path::new("-") {
+        return Ok(Box::new(RawFileReader::from_bufread(BufReader::new(
+            stdin(),
+        ))?));
+    }
+    let is_dat = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("dat"));
+    if is_dat {
+        return Ok(Box::new(DatFileReader::new(path)?));
+    }
+    Ok(Box::new(RawFileReader::new(path)?))
+}
+
+fn formats() -> Result<()> {
+    println!("Format   Decode   Encode");
+    for (event_type, decode, encode) in supported_formats() {
+        let status = |supported: &bool| if *supported { "yes" } else { "no" };
+        println!(
+            "{:<8} {:<8} {}",
+            format!("{:?}", event_type),
+            status(decode),
+            status(encode)
+        );
+    }
+    Ok(())
+}
+
+fn info(path: PathBuf) -> Result<()> {
+    let mut reader = open(&path)?;
+    println!("File: {}", path.display());
+    let header = reader.header();
+    println!("Format: {:?}", header.format);
+    println!(
+        "Geometry: {}x{}",
+        header.camera_geometry.width, header.camera_geometry.height
+    );
+    let mut keys: Vec<_> = header.header_dict.iter().collect();
+    keys.sort();
+    for (key, value) in keys {
+        println!("  {}: {}", key, value);
+    }
+    println!("{:#?}", event_stats(reader.events()));
+    Ok(())
+}
+
+fn dump(path: PathBuf, limit: Option<usize>) -> Result<()> {
+    let mut reader = open(&path)?;
+    let mut out = BufWriter::new(stdout().lock());
+    for event in reader.events().take(limit.unwrap_or(usize::MAX)) {
+        writeln!(out, "{:?}", event)?;
+    }
+    Ok(())
+}
+
+fn convert(path: PathBuf, to: &str, output: Option<PathBuf>, window: Option<u64>) -> Result<()> {
+    let mut reader = open(&path)?;
+    match to {
+        "csv" => match output {
+            Some(output) => write_csv(reader.events(), &mut BufWriter::new(File::create(output)?))?,
+            None => write_csv(reader.events(), &mut BufWriter::new(stdout().lock()))?,
+        },
+        "png-frames" => {
+            let Some(window) = window else {
+                bail!("--window is required to convert to png-frames")
+            };
+            let directory = output.unwrap_or_else(|| PathBuf::from("."));
+            std::fs::create_dir_all(&directory)?;
+            let geometry = reader.header().camera_geometry;
+            if geometry.width == 0 || geometry.height == 0 {
+                bail!("The file header doesn't provide the sensor geometry")
+            }
+            let frames = frame_stream(
+                reader.events(),
+                geometry.width as usize,
+                geometry.height as usize,
+                window,
+            );
+            for (index, frame) in frames.enumerate() {
+                save_frame_png(&frame, &directory.join(format!("frame_{:06}.png", index)))?;
+            }
+        }
+        _ => bail!("Unsupported output format: {}", to),
+    }
+    Ok(())
+}
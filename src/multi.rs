@@ -0,0 +1,161 @@
+use crate::{Event, RawFileHeader, RawFileReader, RawFileReaderError};
+use std::path::{Path, PathBuf};
+
+/// Reads several raw files as a single stream of events.
+///
+/// The files are read in order and the timestamps of each file are offset by
+/// the last timestamp of the previous one, plus an optional gap, so that the
+/// resulting stream stays continuous. All files must share the event type and
+/// geometry of the first one.
+pub struct MultiFileReader {
+    header: RawFileHeader,
+    paths: Vec<PathBuf>,
+    files: std::vec::IntoIter<RawFileReader>,
+    current: Option<<RawFileReader as IntoIterator>::IntoIter>,
+    gap_us: u64,
+    offset: u64,
+    last_t: u64,
+}
+
+impl MultiFileReader {
+    pub fn new(paths: &[&Path]) -> Result<Self, RawFileReaderError> {
+        let readers = paths
+            .iter()
+            .map(|path| RawFileReader::new(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(first) = readers.first() else {
+            return Err(RawFileReaderError::NoInputFiles);
+        };
+        let header = first.header.clone();
+
+        for (path, reader) in paths.iter().zip(&readers).skip(1) {
+            if reader.header.event_type != header.event_type {
+                return Err(RawFileReaderError::IncompatibleFile(
+                    path.to_path_buf(),
+                    format!(
+                        "event type {:?} instead of {:?}",
+                        reader.header.event_type, header.event_type
+                    ),
+                ));
+            }
+            if reader.header.camera_geometry != header.camera_geometry {
+                return Err(RawFileReaderError::IncompatibleFile(
+                    path.to_path_buf(),
+                    format!(
+                        "geometry {}x{} instead of {}x{}",
+                        reader.header.camera_geometry.width,
+                        reader.header.camera_geometry.height,
+                        header.camera_geometry.width,
+                        header.camera_geometry.height
+                    ),
+                ));
+            }
+        }
+
+        Ok(MultiFileReader {
+            header,
+            paths: paths.iter().map(|path| path.to_path_buf()).collect(),
+            files: readers.into_iter(),
+            current: None,
+            gap_us: 0,
+            offset: 0,
+            last_t: 0,
+        })
+    }
+
+    /// Adds `gap_us` microseconds between the end of a file and the start of the next one
+    pub fn with_gap(mut self, gap_us: u64) -> Self {
+        self.gap_us = gap_us;
+        self
+    }
+
+    /// Header of the first file
+    pub fn header(&self) -> &RawFileHeader {
+        &self.header
+    }
+
+    /// Restarts reading from the first file
+    pub fn reset(&mut self) -> Result<(), RawFileReaderError> {
+        let paths: Vec<&Path> = self.paths.iter().map(PathBuf::as_path).collect();
+        *self = Self::new(&paths)?.with_gap(self.gap_us);
+        Ok(())
+    }
+}
+
+impl Iterator for MultiFileReader {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(events) = self.current.as_mut() {
+                if let Some(event) = events.next() {
+                    let event = match event {
+                        Event::CD { x, y, p, t } => Event::CD {
+                            x,
+                            y,
+                            p,
+                            t: t + self.offset,
+                        },
+                        Event::ExternalTrigger { id, p, t } => Event::ExternalTrigger {
+                            id,
+                            p,
+                            t: t + self.offset,
+                        },
+                        Event::Unknown() => Event::Unknown(),
+                    };
+                    if let Some(t) = event.timestamp() {
+                        self.last_t = self.last_t.max(t);
+                    }
+                    return Some(event);
+                }
+                // The next file starts after the last timestamp of this one
+                self.offset = self.last_t + self.gap_us;
+            }
+            self.current = Some(self.files.next()?.into_iter());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concatenate_files() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let single: Vec<Event> = RawFileReader::new(path).unwrap().into_iter().collect();
+        let last_t = single.iter().filter_map(|e| e.timestamp()).max().unwrap();
+
+        let reader = MultiFileReader::new(&[path, path]).unwrap().with_gap(10);
+        let events: Vec<Event> = reader.collect();
+        assert_eq!(events.len(), 2 * single.len());
+
+        let (first_half, second_half) = events.split_at(single.len());
+        assert_eq!(first_half, single.as_slice());
+        for (event, original) in second_half.iter().zip(&single) {
+            if let Some(t) = original.timestamp() {
+                assert_eq!(event.timestamp(), Some(t + last_t + 10));
+            }
+        }
+        let second_timestamps: Vec<u64> =
+            second_half.iter().filter_map(|e| e.timestamp()).collect();
+        assert!(second_timestamps.iter().all(|t| *t > last_t));
+        assert!(second_timestamps.is_sorted());
+    }
+
+    #[test]
+    fn test_mismatched_files() {
+        let result = MultiFileReader::new(&[
+            Path::new("data/openeb/gen4_evt3_hand.raw"),
+            Path::new("data/openeb/blinking_leds.raw"),
+        ]);
+        assert!(matches!(
+            result,
+            Err(RawFileReaderError::IncompatibleFile(..))
+        ));
+        assert!(matches!(
+            MultiFileReader::new(&[]),
+            Err(RawFileReaderError::NoInputFiles)
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{
+    evt_reader::swap_word_bytes, parse_header, EventDecoder, EventSoA, Evt21Decoder, Evt2Decoder,
+    Evt3Decoder, RawEventType, RawFileReader, RawFileReaderError, PROGRESS_INTERVAL,
+};
+use rayon::prelude::*;
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufReader, Read},
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+};
+use zerocopy::FromBytes;
+
+// Minimum number of raw words decoded by a single worker
+const MIN_CHUNK_WORDS: usize = 1 << 16;
+// Number of raw words decoded between two flushes of the event queue
+const DECODE_BATCH_WORDS: usize = 4096;
+
+/// Decoders whose raw stream can be split into chunks decoded independently.
+///
+/// A chunk always starts on a time high word. The decoder state at that point
+/// is rebuilt by feeding only the previous time high words to a fresh decoder,
+/// so any other state carried across words (like the EVT3 row or vector base)
+/// must be set again by the chunk itself before being used. This is what
+/// [`ChunkedDecoder::is_safe_boundary`] checks.
+pub(crate) trait ChunkedDecoder: EventDecoder + Clone + Send {
+    /// Whether the word updates the time base of the decoder
+    fn is_time_high(word: &Self::RawEventType) -> bool;
+
+    /// Whether decoding can start at `words[index]` with a decoder that only
+    /// saw the time high words preceding it
+    fn is_safe_boundary(words: &[Self::RawEventType], index: usize) -> bool {
+        Self::is_time_high(&words[index])
+    }
+}
+
+impl RawFileReader {
+    /// Decodes the whole file on the rayon thread pool, keeping only CD events.
+    ///
+    /// The events are the same, and in the same order, as the ones returned by
+    /// [`RawFileReader::read_events`] on a fresh reader.
+    ///
+    /// `progress` is called with `(done, total)` from the calling thread every
+    /// [`PROGRESS_INTERVAL`] events decoded by the workers and once at the end,
+    /// `done` being the number of CD events decoded so far and `total` the
+    /// estimate of [`RawFileReader::estimate_event_count`]. Returning
+    /// [`ControlFlow::Break`] cancels the chunks not decoded yet and makes
+    /// the call fail with [`RawFileReaderError::Cancelled`].
+    pub fn par_decode(
+        &self,
+        progress: Option<&mut dyn FnMut(u64, u64) -> ControlFlow<()>>,
+    ) -> Result<EventSoA, RawFileReaderError> {
+        let path = self
+            .path
+            .as_deref()
+            .ok_or(RawFileReaderError::SourceNotReopenable)?;
+        let file = File::open(path)
+            .map_err(|e| RawFileReaderError::FileOpenError(path.to_path_buf(), e))?;
+        let mut reader = BufReader::with_capacity(64 * 1024, file);
+        parse_header(&mut reader)?;
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|_e| RawFileReaderError::ReadBytesFailed)?;
+        if !self.header.endianness.is_native() {
+            swap_word_bytes(&mut bytes, self.header.event_type.word_size());
+        }
+
+        match self.header.event_type {
+            RawEventType::Evt2 => par_decode_bytes(&bytes, Evt2Decoder::new(), progress),
+            RawEventType::Evt21 => par_decode_bytes(
+                &bytes,
+                Evt21Decoder::with_width(self.header.camera_geometry.width),
+                progress,
+            ),
+            RawEventType::Evt3 => par_decode_bytes(
+                &bytes,
+                Evt3Decoder::with_width(self.header.camera_geometry.width),
+                progress,
+            ),
+            _ => Err(RawFileReaderError::DecoderNotImplemented(
+                self.header.event_type,
+            )),
+        }
+    }
+}
+
+fn par_decode_bytes<D>(
+    bytes: &[u8],
+    decoder: D,
+    progress: Option<&mut dyn FnMut(u64, u64) -> ControlFlow<()>>,
+) -> Result<EventSoA, RawFileReaderError>
+where
+    D: ChunkedDecoder,
+    D::RawEventType: Sync,
+{
+    let word_size = D::word_size();
+    let words: Vec<D::RawEventType> = bytes
+        .chunks_exact(word_size)
+        .map(|word| D::RawEventType::read_from_bytes(word).unwrap())
+        .collect();
+
+    let chunk_words = (words.len() / (rayon::current_num_threads() * 4)).max(MIN_CHUNK_WORDS);
+
+    // Sequential pre-pass over the time high words, snapshotting the decoder
+    // state at each chunk boundary
+    let mut chunks = vec![(0, decoder.clone())];
+    let mut time_decoder = decoder;
+    let mut no_events = VecDeque::new();
+    let mut next_boundary = chunk_words;
+    for (index, word) in words.iter().enumerate() {
+        if !D::is_time_high(word) {
+            continue;
+        }
+        if index >= next_boundary && D::is_safe_boundary(&words, index) {
+            chunks.push((index, time_decoder.clone()));
+            next_boundary = index + chunk_words;
+        }
+        time_decoder.decode(std::slice::from_ref(word), &mut no_events);
+    }
+
+    let ends: Vec<usize> = chunks
+        .iter()
+        .skip(1)
+        .map(|(start, _)| *start)
+        .chain(std::iter::once(words.len()))
+        .collect();
+
+    // Each worker sends the number of events of its batches, the workers stop
+    // at the next batch once the progress callback cancelled the decoding
+    let cancelled = AtomicBool::new(false);
+    let decode_chunks = |decoded: Option<mpsc::Sender<u64>>| -> Vec<EventSoA> {
+        chunks
+            .into_par_iter()
+            .zip(ends)
+            .map_with(decoded, |decoded, ((start, mut decoder), end)| {
+                let mut soa = EventSoA::default();
+                let mut event_queue = VecDeque::new();
+                for batch in words[start..end].chunks(DECODE_BATCH_WORDS) {
+                    if cancelled.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    decoder.decode(batch, &mut event_queue);
+                    let len = soa.len();
+                    soa.extend(event_queue.drain(..));
+                    if let Some(decoded) = decoded {
+                        let _ = decoded.send((soa.len() - len) as u64);
+                    }
+                }
+                soa
+            })
+            .collect()
+    };
+
+    let total = words.len() as u64;
+    let decoded = match progress {
+        // The callback isn't `Send`, so the workers run on another thread
+        // while this one reports their progress
+        Some(progress) => thread::scope(|scope| {
+            let (sender, receiver) = mpsc::channel();
+            let workers = scope.spawn(|| decode_chunks(Some(sender)));
+            let (mut done, mut reported) = (0, 0);
+            // Ends once every worker dropped its sender
+            for count in receiver {
+                done += count;
+                if done - reported >= PROGRESS_INTERVAL && !cancelled.load(Ordering::Relaxed) {
+                    reported = done;
+                    if progress(done, total).is_break() {
+                        cancelled.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+            let decoded = workers
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            if !cancelled.load(Ordering::Relaxed) && progress(done, total).is_break() {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+            decoded
+        }),
+        None => decode_chunks(None),
+    };
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(RawFileReaderError::Cancelled);
+    }
+
+    let mut soa = EventSoA::with_capacity(decoded.iter().map(EventSoA::len).sum());
+    for chunk in decoded {
+        soa.x.extend(chunk.x);
+        soa.y.extend(chunk.y);
+        soa.p.extend(chunk.p);
+        soa.t.extend(chunk.t);
+    }
+    Ok(soa)
+}
@@ -0,0 +1,108 @@
+use crate::{crop_stage, refractory_predicate, subsample_predicate, Event};
+
+type Stage = Box<dyn FnMut(Event) -> Option<Event> + Send>;
+
+/// Chain of processing stages applied in order to each event.
+///
+/// Each stage maps an event to the event passed to the next stage, or to
+/// `None` to drop it. This is the same as nesting the adaptors of
+/// [`crate::adaptors`] but the chain can be built step by step.
+#[derive(Default)]
+pub struct EventPipeline {
+    stages: Vec<Stage>,
+}
+
+impl EventPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a custom stage
+    pub fn stage<F>(mut self, stage: F) -> Self
+    where
+        F: FnMut(Event) -> Option<Event> + Send + 'static,
+    {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Appends a stage keeping a region of interest, see [`crate::crop`]
+    pub fn crop(self, x0: u16, y0: u16, x1: u16, y1: u16, rebase: bool) -> Self {
+        self.stage(crop_stage(x0, y0, x1, y1, rebase))
+    }
+
+    /// Appends a refractory period stage, see [`crate::refractory_filter`]
+    pub fn refractory(self, width: usize, height: usize, refractory_us: u64) -> Self {
+        let mut keep = refractory_predicate(width, height, refractory_us);
+        self.stage(move |event| keep(&event).then_some(event))
+    }
+
+    /// Appends a stage keeping one CD event out of `keep_every`, see
+    /// [`crate::subsample`]
+    pub fn subsample(self, keep_every: usize) -> Self {
+        let mut keep = subsample_predicate(keep_every);
+        self.stage(move |event| keep(&event).then_some(event))
+    }
+
+    /// Appends a stage keeping the CD events of polarity `p`, see
+    /// [`crate::filter_polarity`]
+    pub fn polarity(self, p: u8) -> Self {
+        self.stage(move |event| {
+            matches!(event, Event::CD { p: polarity, .. } if polarity == p).then_some(event)
+        })
+    }
+
+    /// Number of stages of the pipeline
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Runs `event` through the stages, returns `None` if a stage dropped it
+    pub fn process(&mut self, event: Event) -> Option<Event> {
+        self.stages
+            .iter_mut()
+            .try_fold(event, |event, stage| stage(event))
+    }
+
+    /// Runs every event of `events` through the stages
+    pub fn apply(mut self, events: impl Iterator<Item = Event>) -> impl Iterator<Item = Event> {
+        events.filter_map(move |event| self.process(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crop, filter_polarity, refractory_filter, RawFileReader};
+    use std::path::Path;
+
+    #[test]
+    fn test_pipeline_matches_adaptors() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        let events: Vec<Event> = reader.read_events().take(50_000).collect();
+
+        let expected: Vec<Event> = filter_polarity(
+            refractory_filter(
+                crop(events.iter().copied(), 100, 100, 900, 600, true),
+                800,
+                500,
+                1_000,
+            ),
+            1,
+        )
+        .collect();
+        let pipeline = EventPipeline::new()
+            .crop(100, 100, 900, 600, true)
+            .refractory(800, 500, 1_000)
+            .polarity(1);
+        assert_eq!(pipeline.len(), 3);
+        let piped: Vec<Event> = pipeline.apply(events.into_iter()).collect();
+        assert!(!piped.is_empty());
+        assert_eq!(piped, expected);
+    }
+}
@@ -0,0 +1,456 @@
+use crate::{slice_events, Event, SliceBy};
+
+/// Builds an exponentially decayed time surface from the CD events.
+///
+/// The output is a single channel `width * height` row-major buffer, both
+/// polarities are merged. Each pixel holds `exp(-(t_ref - t_pixel) / tau_us)`
+/// where `t_pixel` is the timestamp of the last event at this pixel and
+/// `t_ref` the timestamp of the last event of the stream. Pixels that never
+/// fired are 0 and events outside the sensor are ignored.
+pub fn time_surface(
+    events: impl Iterator<Item = Event>,
+    width: usize,
+    height: usize,
+    tau_us: f64,
+) -> Vec<f32> {
+    let mut last_timestamps: Vec<Option<u64>> = vec![None; width * height];
+    let mut t_ref = 0;
+
+    for event in events {
+        if let Event::CD { x, y, t, .. } = event {
+            let (x, y) = (x as usize, y as usize);
+            if x < width && y < height {
+                last_timestamps[y * width + x] = Some(t);
+                t_ref = t_ref.max(t);
+            }
+        }
+    }
+
+    last_timestamps
+        .into_iter()
+        .map(|t_pixel| match t_pixel {
+            Some(t_pixel) => (-((t_ref - t_pixel) as f64) / tau_us).exp() as f32,
+            None => 0.0,
+        })
+        .collect()
+}
+
+/// Builds a `num_bins * height * width` voxel grid from the CD events.
+///
+/// The timestamps are normalized to `[0, num_bins - 1]` over the span of the
+/// stream and each event spreads its polarity (+1 or -1) on the two closest
+/// bins with linear interpolation in time, as done in E2VID. Returns an empty
+/// grid when `num_bins` is 0 and a grid of zeros for an empty stream.
+pub fn to_voxel_grid(
+    events: impl Iterator<Item = Event>,
+    width: usize,
+    height: usize,
+    num_bins: usize,
+) -> Vec<f32> {
+    if num_bins == 0 {
+        return Vec::new();
+    }
+    let mut grid = vec![0.0; num_bins * height * width];
+
+    let events: Vec<(usize, usize, u8, u64)> = events
+        .filter_map(|event| match event {
+            Event::CD { x, y, p, t } if (x as usize) < width && (y as usize) < height => {
+                Some((x as usize, y as usize, p, t))
+            }
+            _ => None,
+        })
+        .collect();
+    let (Some(first), Some(last)) = (events.first(), events.last()) else {
+        return grid;
+    };
+    let t_first = first.3;
+    let duration = last.3.saturating_sub(t_first).max(1) as f64;
+
+    for (x, y, p, t) in events {
+        let t_norm = (num_bins - 1) as f64 * t.saturating_sub(t_first) as f64 / duration;
+        let bin = (t_norm.floor() as usize).min(num_bins - 1);
+        let weight = (t_norm - bin as f64) as f32;
+        let polarity = if p == 0 { -1.0 } else { 1.0 };
+        let pixel = y * width + x;
+
+        grid[bin * height * width + pixel] += polarity * (1.0 - weight);
+        if bin + 1 < num_bins {
+            grid[(bin + 1) * height * width + pixel] += polarity * weight;
+        }
+    }
+    grid
+}
+
+/// Counts the CD events of each pixel, in a row-major `width * height` grid.
+///
+/// Both polarities are counted, events outside the sensor are ignored.
+pub fn event_count_map(
+    events: impl Iterator<Item = Event>,
+    width: usize,
+    height: usize,
+) -> Vec<u32> {
+    let mut counts = vec![0; width * height];
+    for event in events {
+        if let Event::CD { x, y, .. } = event {
+            let (x, y) = (x as usize, y as usize);
+            if x < width && y < height {
+                counts[y * width + x] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// 2D accumulation of the CD events of a time window
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    /// Timestamp of the first event of the frame
+    pub t_start: u64,
+    /// Timestamp of the last event of the frame
+    pub t_end: u64,
+    /// Number of events accumulated in the frame
+    pub event_count: usize,
+    /// Row-major signed polarity sum, +1 per positive and -1 per negative event
+    pub data: Vec<i32>,
+}
+
+impl Frame {
+    /// Creates an empty frame
+    pub fn new(width: usize, height: usize) -> Self {
+        Frame {
+            width,
+            height,
+            t_start: 0,
+            t_end: 0,
+            event_count: 0,
+            data: vec![0; width * height],
+        }
+    }
+
+    /// Polarity sum at pixel `(x, y)`
+    pub fn get(&self, x: usize, y: usize) -> i32 {
+        self.data[y * self.width + x]
+    }
+}
+
+/// Accumulates the CD events into a single frame, events outside the sensor are ignored
+pub fn accumulate_frame(events: impl Iterator<Item = Event>, width: usize, height: usize) -> Frame {
+    let mut frame = Frame::new(width, height);
+    for event in events {
+        if let Event::CD { x, y, p, t } = event {
+            let (x, y) = (x as usize, y as usize);
+            if x >= width || y >= height {
+                continue;
+            }
+            if frame.event_count == 0 {
+                frame.t_start = t;
+            }
+            frame.t_end = t;
+            frame.event_count += 1;
+            frame.data[y * width + x] += if p == 0 { -1 } else { 1 };
+        }
+    }
+    frame
+}
+
+/// Pixel type of the buffers filled by [`accumulate_frame_into`]
+pub trait FramePixel: Copy {
+    /// Adds a CD event of polarity `p` to the pixel
+    fn accumulate(&mut self, p: u8);
+
+    /// Called once all the events are accumulated
+    fn finalize(_buf: &mut [Self]) {}
+}
+
+/// Event count, saturating at `u8::MAX`. Non zero pixels make a binary mask.
+impl FramePixel for u8 {
+    fn accumulate(&mut self, _p: u8) {
+        *self = self.saturating_add(1);
+    }
+}
+
+/// Signed polarity sum, saturating at the bounds of `i16`
+impl FramePixel for i16 {
+    fn accumulate(&mut self, p: u8) {
+        *self = self.saturating_add(if p == 0 { -1 } else { 1 });
+    }
+}
+
+/// Signed polarity sum normalized to `[-1, 1]` by the largest absolute value
+impl FramePixel for f32 {
+    fn accumulate(&mut self, p: u8) {
+        *self += if p == 0 { -1.0 } else { 1.0 };
+    }
+
+    fn finalize(buf: &mut [Self]) {
+        let max = buf.iter().fold(0.0f32, |max, value| max.max(value.abs()));
+        if max > 0.0 {
+            buf.iter_mut().for_each(|value| *value /= max);
+        }
+    }
+}
+
+/// Accumulates the CD events into a row-major `width * height` buffer of any
+/// [`FramePixel`] type, events outside the sensor are ignored.
+///
+/// The buffer isn't cleared first so that several streams can be
+/// accumulated in the same frame, although the `f32` normalization only
+/// holds for the last call. Returns the number of events accumulated.
+/// Panics if `buf` doesn't hold `width * height` pixels.
+pub fn accumulate_frame_into<T: FramePixel>(
+    events: impl Iterator<Item = Event>,
+    width: usize,
+    height: usize,
+    buf: &mut [T],
+) -> usize {
+    assert_eq!(
+        buf.len(),
+        width * height,
+        "buffer size doesn't match the frame size"
+    );
+    let mut count = 0;
+    for event in events {
+        if let Event::CD { x, y, p, .. } = event {
+            let (x, y) = (x as usize, y as usize);
+            if x < width && y < height {
+                buf[y * width + x].accumulate(p);
+                count += 1;
+            }
+        }
+    }
+    T::finalize(buf);
+    count
+}
+
+/// Accumulates the CD events into one frame per time window of `window_us` microseconds.
+///
+/// The windows are the ones of [`slice_events`] with [`SliceBy::Time`].
+pub fn frame_stream(
+    events: impl Iterator<Item = Event>,
+    width: usize,
+    height: usize,
+    window_us: u64,
+) -> impl Iterator<Item = Frame> {
+    slice_events(events, SliceBy::Time(window_us))
+        .map(move |slice| accumulate_frame(slice.into_iter(), width, height))
+}
+
+/// Difference between each pair of consecutive frames, for change detection.
+///
+/// Each output frame holds the per-pixel polarity sum of a frame minus the
+/// one of the frame before it, so non zero pixels are the ones whose activity
+/// changed. It spans both frames: `t_start` is the one of the earlier frame,
+/// `t_end` and `event_count` are the ones of the later frame. A stream of `n`
+/// frames gives `n - 1` differences. Panics if the frames sizes differ.
+pub fn frame_diff_stream(frames: impl Iterator<Item = Frame>) -> impl Iterator<Item = Frame> {
+    frames
+        .scan(None, |previous: &mut Option<Frame>, frame| {
+            let diff = previous.take().map(|previous| {
+                assert!(
+                    previous.width == frame.width && previous.height == frame.height,
+                    "frame sizes don't match"
+                );
+                Frame {
+                    width: frame.width,
+                    height: frame.height,
+                    t_start: previous.t_start,
+                    t_end: frame.t_end,
+                    event_count: frame.event_count,
+                    data: frame
+                        .data
+                        .iter()
+                        .zip(&previous.data)
+                        .map(|(current, previous)| current - previous)
+                        .collect(),
+                }
+            });
+            *previous = Some(frame);
+            Some(diff)
+        })
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_surface() {
+        let events = vec![
+            Event::CD {
+                x: 0,
+                y: 0,
+                p: 1,
+                t: 0,
+            },
+            Event::CD {
+                x: 1,
+                y: 1,
+                p: 0,
+                t: 99_990,
+            },
+            Event::CD {
+                x: 2,
+                y: 1,
+                p: 1,
+                t: 100_000,
+            },
+            Event::CD {
+                x: 10,
+                y: 10,
+                p: 1,
+                t: 100_000,
+            },
+        ];
+        let surface = time_surface(events.into_iter(), 3, 2, 1_000.0);
+        let at = |x: usize, y: usize| surface[y * 3 + x];
+        assert_eq!(surface.len(), 6);
+        assert_eq!(at(2, 1), 1.0);
+        assert!(at(1, 1) > 0.98);
+        assert!(at(0, 0) < 1e-6);
+        assert_eq!(at(1, 0), 0.0);
+    }
+
+    #[test]
+    fn test_voxel_grid() {
+        let events = vec![
+            Event::CD {
+                x: 0,
+                y: 0,
+                p: 1,
+                t: 1_000,
+            },
+            Event::CD {
+                x: 0,
+                y: 1,
+                p: 1,
+                t: 1_025,
+            },
+            Event::CD {
+                x: 1,
+                y: 1,
+                p: 0,
+                t: 1_050,
+            },
+            Event::CD {
+                x: 1,
+                y: 0,
+                p: 0,
+                t: 1_100,
+            },
+        ];
+        let grid = to_voxel_grid(events.into_iter(), 2, 2, 3);
+        let at = |bin: usize, x: usize, y: usize| grid[bin * 4 + y * 2 + x];
+        assert_eq!(grid.len(), 12);
+        assert_eq!(at(0, 0, 0), 1.0);
+        // Halfway between the first and second bins
+        assert_eq!(at(0, 0, 1), 0.5);
+        assert_eq!(at(1, 0, 1), 0.5);
+        // Exactly on the second bin
+        assert_eq!(at(0, 1, 1), 0.0);
+        assert_eq!(at(1, 1, 1), -1.0);
+        assert_eq!(at(2, 1, 0), -1.0);
+        assert_eq!(grid.iter().sum::<f32>(), 0.0);
+    }
+
+    #[test]
+    fn test_voxel_grid_edge_cases() {
+        assert!(to_voxel_grid(std::iter::empty(), 2, 2, 0).is_empty());
+        assert_eq!(to_voxel_grid(std::iter::empty(), 2, 2, 3), vec![0.0; 12]);
+    }
+
+    #[test]
+    fn test_frame_stream() {
+        // Three events every 100us, alternating pixels and polarities
+        let events: Vec<Event> = (0..30u64)
+            .map(|i| Event::cd((i % 2) as u16, 1, (i % 3 == 0) as u8, i * 100))
+            .collect();
+        let window_us = 1_000;
+
+        let slices: Vec<crate::EventSlice> =
+            slice_events(events.clone().into_iter(), SliceBy::Time(window_us)).collect();
+        let frames: Vec<Frame> = frame_stream(events.into_iter(), 2, 2, window_us).collect();
+        assert_eq!(frames.len(), slices.len());
+
+        for (frame, slice) in frames.iter().zip(&slices) {
+            assert_eq!(frame.event_count, slice.len());
+            assert_eq!(frame.t_start, slice[0].timestamp().unwrap());
+            assert_eq!(frame.t_end, slice.last().unwrap().timestamp().unwrap());
+            for x in 0..2 {
+                let expected: i32 = slice
+                    .iter()
+                    .filter(|e| e.x() == Some(x as u16))
+                    .map(|e| if e.polarity() == Some(0) { -1 } else { 1 })
+                    .sum();
+                assert_eq!(frame.get(x, 1), expected);
+                assert_eq!(frame.get(x, 0), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_frame_diff_stream() {
+        let frame = |data: Vec<i32>, t_start, t_end| Frame {
+            width: 2,
+            height: 2,
+            t_start,
+            t_end,
+            event_count: data.iter().map(|v| v.unsigned_abs() as usize).sum(),
+            data,
+        };
+        let first = frame(vec![1, 0, -2, 3], 0, 99);
+        let second = frame(vec![1, 4, 0, -1], 100, 199);
+
+        let diffs: Vec<Frame> =
+            frame_diff_stream([first.clone(), second.clone()].into_iter()).collect();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].data, vec![0, 4, 2, -4]);
+        assert_eq!((diffs[0].t_start, diffs[0].t_end), (0, 199));
+        assert_eq!(diffs[0].event_count, second.event_count);
+
+        assert_eq!(frame_diff_stream([first].into_iter()).count(), 0);
+    }
+
+    #[test]
+    fn test_accumulate_frame_into() {
+        let events = (0..300).map(|i| Event::cd(0, 0, 1, i)).chain([
+            Event::cd(1, 0, 0, 300),
+            Event::cd(1, 0, 0, 301),
+            Event::cd(1, 0, 1, 302),
+            Event::cd(5, 5, 1, 303),
+        ]);
+        let events: Vec<Event> = events.collect();
+
+        let mut counts = vec![0u8; 4];
+        assert_eq!(
+            accumulate_frame_into(events.iter().copied(), 2, 2, &mut counts),
+            303
+        );
+        assert_eq!(counts, vec![u8::MAX, 3, 0, 0]);
+
+        let mut sums = vec![0i16; 4];
+        accumulate_frame_into(events.iter().copied(), 2, 2, &mut sums);
+        assert_eq!(sums, vec![300, -1, 0, 0]);
+
+        let mut normalized = vec![0f32; 4];
+        accumulate_frame_into(events.iter().copied(), 2, 2, &mut normalized);
+        assert_eq!(normalized, vec![1.0, -1.0 / 300.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_event_count_map() {
+        let events = [
+            Event::cd(0, 0, 1, 10),
+            Event::cd(2, 1, 0, 11),
+            Event::trigger(0, 1, 12),
+            Event::cd(2, 1, 1, 13),
+            Event::cd(3, 0, 1, 14),
+            Event::cd(0, 2, 1, 15),
+            Event::cd(2, 1, 0, 16),
+        ];
+        let counts = event_count_map(events.into_iter(), 3, 2);
+        assert_eq!(counts, vec![1, 0, 0, 0, 0, 3]);
+    }
+}
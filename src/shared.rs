@@ -0,0 +1,90 @@
+use crate::{DynamicEvtReader, Event, RawFileReader};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Iterator over the events of a reader that can be shared between threads.
+///
+/// The readers are `Send` and can be moved to another thread to be iterated
+/// there, but iterating needs `&mut` access. Clones of this iterator share the
+/// same decoder behind a mutex, each event is yielded to exactly one of them.
+/// Events are yielded in stream order, but the order in which the threads
+/// observe them depends on the scheduling.
+#[derive(Clone)]
+pub struct SharedEventIterator {
+    inner: Arc<Mutex<DynamicEvtReader>>,
+}
+
+impl SharedEventIterator {
+    pub fn new(events: DynamicEvtReader) -> Self {
+        SharedEventIterator {
+            inner: Arc::new(Mutex::new(events)),
+        }
+    }
+
+    /// Takes up to `n` events at once, locking the decoder a single time.
+    /// This is cheaper than calling `next` repeatedly when the decoder is
+    /// contended.
+    pub fn next_batch(&self, n: usize) -> Vec<Event> {
+        let mut events = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        events.by_ref().take(n).collect()
+    }
+}
+
+impl From<RawFileReader> for SharedEventIterator {
+    fn from(reader: RawFileReader) -> Self {
+        Self::new(reader.into_iter())
+    }
+}
+
+impl Iterator for SharedEventIterator {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        // A panic of another thread while decoding leaves the decoder usable
+        self.inner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{path::Path, thread};
+
+    #[test]
+    fn test_shared_event_iterator() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let expected: Vec<Event> = RawFileReader::new(path)
+            .expect("Failed to open test file")
+            .read_events()
+            .collect();
+
+        let shared = SharedEventIterator::from(RawFileReader::new(path).unwrap());
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    let mut events = Vec::new();
+                    loop {
+                        let batch = shared.next_batch(1000);
+                        if batch.is_empty() {
+                            return events;
+                        }
+                        events.extend(batch);
+                    }
+                })
+            })
+            .collect();
+        let mut events: Vec<Event> = workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect();
+
+        assert_eq!(events.len(), expected.len());
+        events.sort();
+        let mut expected = expected;
+        expected.sort();
+        assert_eq!(events, expected);
+    }
+}
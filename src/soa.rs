@@ -0,0 +1,145 @@
+use crate::{Event, RawFileReader};
+use std::{ops::Deref, sync::Arc};
+
+/// CD events stored in column form, one vector per field
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EventSoA {
+    pub x: Vec<u16>,
+    pub y: Vec<u16>,
+    pub p: Vec<u8>,
+    pub t: Vec<u64>,
+}
+
+impl EventSoA {
+    pub fn with_capacity(capacity: usize) -> Self {
+        EventSoA {
+            x: Vec::with_capacity(capacity),
+            y: Vec::with_capacity(capacity),
+            p: Vec::with_capacity(capacity),
+            t: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Number of CD events
+    pub fn len(&self) -> usize {
+        self.t.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.t.is_empty()
+    }
+
+    /// Appends an event, ignoring anything that isn't a CD event
+    pub fn push(&mut self, event: Event) {
+        if let Event::CD { x, y, p, t } = event {
+            self.x.push(x);
+            self.y.push(y);
+            self.p.push(p);
+            self.t.push(t);
+        }
+    }
+
+    /// Returns the CD event stored at `index`
+    pub fn get(&self, index: usize) -> Option<Event> {
+        if index >= self.len() {
+            return None;
+        }
+        Some(Event::CD {
+            x: self.x[index],
+            y: self.y[index],
+            p: self.p[index],
+            t: self.t[index],
+        })
+    }
+
+    /// Iterates over the stored CD events in order
+    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
+        (0..self.len()).map(|index| Event::CD {
+            x: self.x[index],
+            y: self.y[index],
+            p: self.p[index],
+            t: self.t[index],
+        })
+    }
+}
+
+impl FromIterator<Event> for EventSoA {
+    fn from_iter<I: IntoIterator<Item = Event>>(iter: I) -> Self {
+        let mut soa = EventSoA::default();
+        soa.extend(iter);
+        soa
+    }
+}
+
+impl Extend<Event> for EventSoA {
+    fn extend<I: IntoIterator<Item = Event>>(&mut self, iter: I) {
+        iter.into_iter().for_each(|event| self.push(event));
+    }
+}
+
+/// Decodes the remaining events of the reader, keeping only CD events in column form
+pub fn decode_to_soa(mut reader: RawFileReader) -> EventSoA {
+    reader.read_events().collect()
+}
+
+/// Columns of the CD events as `(x, y, p, t)` arrays
+#[cfg(feature = "ndarray")]
+pub type EventArrays = (
+    ndarray::Array1<u16>,
+    ndarray::Array1<u16>,
+    ndarray::Array1<u8>,
+    ndarray::Array1<u64>,
+);
+
+#[cfg(feature = "ndarray")]
+impl EventSoA {
+    /// Converts the columns to `(x, y, p, t)` arrays, without copying them
+    pub fn into_ndarray(self) -> EventArrays {
+        (
+            ndarray::Array1::from_vec(self.x),
+            ndarray::Array1::from_vec(self.y),
+            ndarray::Array1::from_vec(self.p),
+            ndarray::Array1::from_vec(self.t),
+        )
+    }
+}
+
+/// Decodes the remaining CD events of the reader into `(x, y, p, t)`
+/// arrays, the Rust counterpart of the numpy export
+#[cfg(feature = "ndarray")]
+pub fn decode_to_ndarray(reader: RawFileReader) -> EventArrays {
+    decode_to_soa(reader).into_ndarray()
+}
+
+/// CD events decoded once and shared between consumers, possibly on other
+/// threads, created by [`decode_shared`].
+///
+/// Cloning only bumps a reference count, the events are never copied.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DecodedEvents(Arc<EventSoA>);
+
+impl DecodedEvents {
+    /// Iterates over the shared CD events in order
+    pub fn iter(&self) -> impl Iterator<Item = Event> + '_ {
+        self.0.iter()
+    }
+}
+
+impl From<EventSoA> for DecodedEvents {
+    fn from(soa: EventSoA) -> Self {
+        DecodedEvents(Arc::new(soa))
+    }
+}
+
+impl Deref for DecodedEvents {
+    type Target = EventSoA;
+
+    fn deref(&self) -> &EventSoA {
+        &self.0
+    }
+}
+
+/// Same as [`decode_to_soa`] but the events can be shared between threads
+pub fn decode_shared(reader: RawFileReader) -> DecodedEvents {
+    decode_to_soa(reader).into()
+}
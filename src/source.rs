@@ -0,0 +1,157 @@
+use crate::{
+    CameraGeometry, DatFileReader, Event, MultiFileReader, RawEventType, RawFileReader,
+    RawFileReaderError,
+};
+use std::collections::HashMap;
+
+/// File format of an event stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFormat {
+    /// Raw data of the given event type
+    Raw(RawEventType),
+    /// CD records of a DAT file
+    Dat,
+}
+
+/// Description of an event stream shared by every file format
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamHeader<'a> {
+    pub format: StreamFormat,
+    pub camera_geometry: CameraGeometry,
+    /// Key/value pairs of the file header
+    pub header_dict: &'a HashMap<String, String>,
+}
+
+/// Common interface of the readers producing a stream of events
+pub trait EventSource {
+    /// Header describing the stream
+    fn header(&self) -> StreamHeader<'_>;
+
+    /// Remaining events of the stream
+    fn events(&mut self) -> Box<dyn Iterator<Item = Event> + '_>;
+
+    /// Restarts the stream from its first event
+    fn reset(&mut self) -> Result<(), RawFileReaderError>;
+}
+
+impl EventSource for RawFileReader {
+    fn header(&self) -> StreamHeader<'_> {
+        StreamHeader {
+            format: StreamFormat::Raw(self.header.event_type),
+            camera_geometry: self.header.camera_geometry,
+            header_dict: &self.header.header_dict,
+        }
+    }
+
+    fn events(&mut self) -> Box<dyn Iterator<Item = Event> + '_> {
+        Box::new(self.read_events())
+    }
+
+    fn reset(&mut self) -> Result<(), RawFileReaderError> {
+        RawFileReader::reset(self)
+    }
+}
+
+impl EventSource for MultiFileReader {
+    fn header(&self) -> StreamHeader<'_> {
+        let header = MultiFileReader::header(self);
+        StreamHeader {
+            format: StreamFormat::Raw(header.event_type),
+            camera_geometry: header.camera_geometry,
+            header_dict: &header.header_dict,
+        }
+    }
+
+    fn events(&mut self) -> Box<dyn Iterator<Item = Event> + '_> {
+        Box::new(self)
+    }
+
+    fn reset(&mut self) -> Result<(), RawFileReaderError> {
+        MultiFileReader::reset(self)
+    }
+}
+
+impl EventSource for DatFileReader {
+    fn header(&self) -> StreamHeader<'_> {
+        StreamHeader {
+            format: StreamFormat::Dat,
+            camera_geometry: self.header.camera_geometry,
+            header_dict: &self.header.header_dict,
+        }
+    }
+
+    fn events(&mut self) -> Box<dyn Iterator<Item = Event> + '_> {
+        Box::new(self.read_events())
+    }
+
+    fn reset(&mut self) -> Result<(), RawFileReaderError> {
+        DatFileReader::reset(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn count_cd(source: &mut impl EventSource) -> usize {
+        source.events().filter(Event::is_cd).count()
+    }
+
+    #[test]
+    fn test_generic_event_source() {
+        let path = Path::new("data/openeb/gen4_evt3_hand.raw");
+        let mut reader = RawFileReader::new(path).expect("Failed to open test file");
+        assert_eq!(
+            reader.header().format,
+            StreamFormat::Raw(RawEventType::Evt3)
+        );
+
+        let count = count_cd(&mut reader);
+        assert!(count > 0);
+        assert_eq!(count_cd(&mut reader), 0);
+        EventSource::reset(&mut reader).unwrap();
+        assert_eq!(count_cd(&mut reader), count);
+
+        let mut sources: Vec<Box<dyn EventSource>> = vec![
+            Box::new(reader),
+            Box::new(MultiFileReader::new(&[path, path]).unwrap()),
+        ];
+        for source in &mut sources {
+            source.reset().unwrap();
+        }
+        assert_eq!(sources[1].events().filter(Event::is_cd).count(), 2 * count);
+    }
+
+    #[test]
+    fn test_dat_event_source() {
+        // Two CD records of a 640x480 sensor, see the layout in dat.rs
+        let mut bytes = b"% Height 480\n% Width 640\n".to_vec();
+        bytes.extend([0x0C, 8]);
+        for (x, y, p, t) in [(12u32, 34u32, 1u32, 100u32), (639, 479, 0, 150)] {
+            bytes.extend(t.to_le_bytes());
+            bytes.extend((x | y << 14 | p << 28).to_le_bytes());
+        }
+        let path = std::env::temp_dir().join(format!("libreeb_source_{}.dat", std::process::id()));
+        std::fs::write(&path, bytes).expect("Failed to write test file");
+
+        let mut source: Box<dyn EventSource> =
+            Box::new(DatFileReader::new(&path).expect("Failed to open test file"));
+        let header = source.header();
+        assert_eq!(header.format, StreamFormat::Dat);
+        assert_eq!(
+            header.camera_geometry,
+            CameraGeometry {
+                width: 640,
+                height: 480
+            }
+        );
+        assert_eq!(header.header_dict["Width"], "640");
+
+        let expected = vec![Event::cd(12, 34, 1, 100), Event::cd(639, 479, 0, 150)];
+        assert_eq!(source.events().collect::<Vec<_>>(), expected);
+        source.reset().unwrap();
+        assert_eq!(source.events().collect::<Vec<_>>(), expected);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,214 @@
+use crate::Event;
+use pyo3::prelude::*;
+
+/// Summary of a stream of events
+#[pyclass(get_all)]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EventStats {
+    /// Number of CD events
+    pub cd_count: u64,
+    /// Number of CD events with positive polarity
+    pub positive_count: u64,
+    /// Number of CD events with negative polarity
+    pub negative_count: u64,
+    /// Number of external trigger events
+    pub trigger_count: u64,
+    /// Smallest timestamp of the CD events, in microseconds
+    pub min_t: Option<u64>,
+    /// Largest timestamp of the CD events, in microseconds
+    pub max_t: Option<u64>,
+    /// `max_t - min_t`, in microseconds
+    pub duration_us: u64,
+    /// Mean CD event rate over the duration, in events per second
+    pub event_rate: f64,
+    /// Bounding box of the active pixels as `(min_x, min_y, max_x, max_y)`
+    pub bounding_box: Option<(u16, u16, u16, u16)>,
+}
+
+#[pymethods]
+impl EventStats {
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// Consumes the events and computes their [`EventStats`]
+pub fn event_stats(events: impl Iterator<Item = Event>) -> EventStats {
+    let mut stats = EventStats::default();
+
+    for event in events {
+        match event {
+            Event::CD { x, y, p, t } => {
+                stats.min_t = Some(stats.min_t.map_or(t, |min_t| min_t.min(t)));
+                stats.max_t = Some(stats.max_t.map_or(t, |max_t| max_t.max(t)));
+                stats.cd_count += 1;
+                if p == 0 {
+                    stats.negative_count += 1;
+                } else {
+                    stats.positive_count += 1;
+                }
+                stats.bounding_box = Some(match stats.bounding_box {
+                    Some((min_x, min_y, max_x, max_y)) => {
+                        (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                    }
+                    None => (x, y, x, y),
+                });
+            }
+            Event::ExternalTrigger { .. } => stats.trigger_count += 1,
+            Event::Unknown() => {}
+        }
+    }
+
+    if let (Some(min_t), Some(max_t)) = (stats.min_t, stats.max_t) {
+        stats.duration_us = max_t - min_t;
+    }
+    if stats.duration_us > 0 {
+        stats.event_rate = stats.cd_count as f64 / (stats.duration_us as f64 * 1e-6);
+    }
+
+    stats
+}
+
+/// Number of events of each type in a stream
+#[pyclass(get_all)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EventTypeCounts {
+    /// Number of CD events with positive polarity
+    pub cd_pos: u64,
+    /// Number of CD events with negative polarity
+    pub cd_neg: u64,
+    /// Number of external trigger events
+    pub triggers: u64,
+    /// Number of unknown events
+    pub unknown: u64,
+}
+
+#[pymethods]
+impl EventTypeCounts {
+    /// Number of events of all types
+    pub fn total(&self) -> u64 {
+        self.cd_pos + self.cd_neg + self.triggers + self.unknown
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// Consumes the events and counts them by type, which is cheaper than
+/// [`event_stats`] when only the counts are needed
+pub fn count_by_type(events: impl Iterator<Item = Event>) -> EventTypeCounts {
+    events.fold(EventTypeCounts::default(), |mut counts, event| {
+        match event {
+            Event::CD { p: 0, .. } => counts.cd_neg += 1,
+            Event::CD { .. } => counts.cd_pos += 1,
+            Event::ExternalTrigger { .. } => counts.triggers += 1,
+            Event::Unknown() => counts.unknown += 1,
+        }
+        counts
+    })
+}
+
+/// Counts the events of consecutive windows of `window_us` microseconds,
+/// yielding `(window_start, count)` pairs to plot the event rate over time.
+///
+/// The first window starts at the first timestamped event, and the windows
+/// without events are reported with a count of 0, so a gap in the stream
+/// gives one pair per window it spans. Events without a timestamp aren't
+/// counted.
+pub fn event_rate(
+    events: impl Iterator<Item = Event>,
+    window_us: u64,
+) -> impl Iterator<Item = (u64, u64)> {
+    let window_us = window_us.max(1);
+    let mut timestamps = events.filter_map(|event| event.timestamp()).peekable();
+    let mut next_start = None;
+    std::iter::from_fn(move || {
+        let first = *timestamps.peek()?;
+        let start = next_start.unwrap_or(first);
+        let end = start + window_us;
+        let mut count = 0;
+        while timestamps.next_if(|&t| t < end).is_some() {
+            count += 1;
+        }
+        next_start = Some(end);
+        Some((start, count))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_stats() {
+        let events = vec![
+            Event::CD {
+                x: 10,
+                y: 5,
+                p: 1,
+                t: 1_000,
+            },
+            Event::ExternalTrigger { id: 0, p: 1, t: 0 },
+            Event::CD {
+                x: 3,
+                y: 20,
+                p: 0,
+                t: 1_500,
+            },
+            Event::Unknown(),
+            Event::CD {
+                x: 7,
+                y: 9,
+                p: 1,
+                t: 3_000,
+            },
+        ];
+        let stats = event_stats(events.into_iter());
+        assert_eq!(stats.cd_count, 3);
+        assert_eq!(stats.positive_count, 2);
+        assert_eq!(stats.negative_count, 1);
+        assert_eq!(stats.trigger_count, 1);
+        assert_eq!(stats.min_t, Some(1_000));
+        assert_eq!(stats.max_t, Some(3_000));
+        assert_eq!(stats.duration_us, 2_000);
+        assert_eq!(stats.event_rate, 1_500.0);
+        assert_eq!(stats.bounding_box, Some((3, 5, 10, 20)));
+    }
+
+    #[test]
+    fn test_event_rate() {
+        // One event every 100us, a burst of 50 events in [5050us, 5100us[
+        // and nothing in [12000us, 14000us[
+        let steady = (0..120).chain(140..150).map(|i| i * 100);
+        let burst = 5_050..5_100;
+        let mut timestamps: Vec<u64> = steady.chain(burst).collect();
+        timestamps.sort();
+        let events = timestamps
+            .into_iter()
+            .map(|t| Event::cd(0, 0, 1, t + 1_000));
+
+        let rate: Vec<(u64, u64)> = event_rate(events, 1_000).collect();
+        assert_eq!(rate.len(), 15);
+        assert!(rate
+            .iter()
+            .enumerate()
+            .all(|(i, &(start, _))| start == 1_000 + i as u64 * 1_000));
+        assert_eq!(rate[5], (6_000, 60));
+        assert_eq!(
+            rate.iter().max_by_key(|(_, count)| count),
+            Some(&(6_000, 60))
+        );
+        assert_eq!(rate[12].1, 0);
+        assert_eq!(rate[13].1, 0);
+        assert_eq!(rate.iter().map(|(_, count)| count).sum::<u64>(), 180);
+
+        assert_eq!(event_rate(std::iter::empty(), 1_000).count(), 0);
+    }
+
+    #[test]
+    fn test_event_stats_empty() {
+        let stats = event_stats(std::iter::empty());
+        assert_eq!(stats, EventStats::default());
+    }
+}
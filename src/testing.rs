@@ -0,0 +1,186 @@
+//! In-memory raw recordings built from a list of events, to test code reading
+//! raw data without depending on sample files.
+//!
+//! The buffers hold a header followed by the encoded events and can be decoded
+//! with [`crate::RawFileReader::from_bufread`]. The geometry of the header is
+//! the smallest one holding every CD event. The events must be sorted by
+//! timestamp, and the [`Event::Unknown`] events are skipped.
+//!
+//! Only built for the tests of this crate or with the `test-util` feature.
+use crate::{Event, Evt3Encoder};
+
+// Event types of the EVT2 and EVT2.1 words, which share their values
+const CD_OFF: u64 = 0b0000;
+const CD_ON: u64 = 0b0001;
+const EVT_TIME_HIGH: u64 = 0b1000;
+const EXT_TRIGGER: u64 = 0b1010;
+
+// Both formats store bits 33..6 of the timestamps in their time high words
+const TIME_HIGH_BITS: u32 = 28;
+const TIME_HIGH_MASK: u64 = (1 << TIME_HIGH_BITS) - 1;
+
+/// Encodes `events` as an EVT2 recording
+pub fn synthetic_evt2(events: &[Event]) -> Vec<u8> {
+    let mut out = header(events, "2.0", "EVT2");
+    let mut time_high = TimeHighWriter::default();
+    let push = |out: &mut Vec<u8>, word: u64| out.extend((word as u32).to_le_bytes());
+    for event in events {
+        let Some(t) = time_high.write(event, |word| push(&mut out, EVT_TIME_HIGH << 28 | word))
+        else {
+            continue;
+        };
+        let time_low = (t & 0x3F) << 22;
+        match *event {
+            Event::CD { x, y, p, .. } => {
+                let event_type = if p == 0 { CD_OFF } else { CD_ON };
+                let address = (x as u64 & 0x7FF) << 11 | y as u64 & 0x7FF;
+                push(&mut out, event_type << 28 | time_low | address);
+            }
+            Event::ExternalTrigger { id, p, .. } => {
+                let trigger = (id as u64 & 0x1F) << 8 | p as u64 & 1;
+                push(&mut out, EXT_TRIGGER << 28 | time_low | trigger);
+            }
+            Event::Unknown() => {}
+        }
+    }
+    out
+}
+
+/// Encodes `events` as an EVT2.1 recording, with one vector word per CD event
+pub fn synthetic_evt21(events: &[Event]) -> Vec<u8> {
+    let mut out = header(events, "2.1", "EVT21");
+    let mut time_high = TimeHighWriter::default();
+    let push = |out: &mut Vec<u8>, word: u64| out.extend(word.to_le_bytes());
+    for event in events {
+        let Some(t) = time_high.write(event, |word| {
+            push(&mut out, EVT_TIME_HIGH << 60 | word << 32)
+        }) else {
+            continue;
+        };
+        let time_low = (t & 0x3F) << 54;
+        match *event {
+            Event::CD { x, y, p, .. } => {
+                let event_type = if p == 0 { CD_OFF } else { CD_ON };
+                let address = (x as u64 & 0x7FF) << 43 | (y as u64 & 0x7FF) << 32;
+                push(&mut out, event_type << 60 | time_low | address | 1);
+            }
+            Event::ExternalTrigger { id, p, .. } => {
+                let trigger = (id as u64 & 0x1F) << 40 | (p as u64 & 1) << 32;
+                push(&mut out, EXT_TRIGGER << 60 | time_low | trigger);
+            }
+            Event::Unknown() => {}
+        }
+    }
+    out
+}
+
+/// Encodes `events` as an EVT3 recording
+pub fn synthetic_evt3(events: &[Event]) -> Vec<u8> {
+    let mut out = header(events, "3.0", "EVT3");
+    let mut encoder = Evt3Encoder::new();
+    for event in events {
+        encoder.encode(event, &mut out);
+    }
+    out
+}
+
+fn header(events: &[Event], evt: &str, format: &str) -> Vec<u8> {
+    let mut header = format!("% evt {evt}\n% format {format}\n");
+    let (width, height) = events
+        .iter()
+        .filter_map(|event| match *event {
+            Event::CD { x, y, .. } => Some((x as u32 + 1, y as u32 + 1)),
+            _ => None,
+        })
+        .fold((0, 0), |(width, height), (x, y)| {
+            (width.max(x), height.max(y))
+        });
+    if width != 0 {
+        header += &format!("% geometry {width}x{height}\n");
+    }
+    header += "% end\n";
+    header.into_bytes()
+}
+
+// Writes the time high words of the EVT2 and EVT2.1 formats. As done by
+// `Evt3Encoder`, a stream starting past the first time loop starts with a time
+// high of 0 followed by the loops crossed, which the decoders count.
+#[derive(Default)]
+struct TimeHighWriter {
+    time_high: Option<u64>, // Time high of the last word, including the time loops
+}
+
+impl TimeHighWriter {
+    /// Calls `push` with the payload of the time high words needed before
+    /// `event`, returns its timestamp or None when it has none
+    fn write(&mut self, event: &Event, mut push: impl FnMut(u64)) -> Option<u64> {
+        let t = event.timestamp()?;
+        let time_high = t >> 6;
+        if self.time_high == Some(time_high) {
+            return Some(t);
+        }
+        let mut current = self.time_high;
+        if current.is_none() && time_high >> TIME_HIGH_BITS > 0 {
+            push(0);
+            current = Some(0);
+        }
+        if let Some(mut current) = current {
+            while current >> TIME_HIGH_BITS < time_high >> TIME_HIGH_BITS {
+                if current & TIME_HIGH_MASK != TIME_HIGH_MASK {
+                    push(TIME_HIGH_MASK);
+                }
+                push(0);
+                current = ((current >> TIME_HIGH_BITS) + 1) << TIME_HIGH_BITS;
+            }
+        }
+        push(time_high & TIME_HIGH_MASK);
+        self.time_high = Some(time_high);
+        Some(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CameraGeometry, RawEventType, RawFileReader};
+    use std::io;
+
+    #[test]
+    fn test_synthetic_round_trip() {
+        let time_loop = 1 << 34;
+        let events = vec![
+            Event::cd(3, 4, 1, 10),
+            Event::cd(639, 0, 0, 10),
+            Event::trigger(2, 1, 70),
+            Event::Unknown(),
+            Event::cd(0, 479, 1, 5_000),
+            Event::cd(7, 7, 0, 20_000_000),
+            Event::trigger(0, 0, time_loop + 3),
+            Event::cd(1, 2, 1, 3 * time_loop + 100),
+        ];
+        let expected: Vec<Event> = events
+            .iter()
+            .copied()
+            .filter(|e| *e != Event::Unknown())
+            .collect();
+
+        for (bytes, event_type) in [
+            (synthetic_evt2(&events), RawEventType::Evt2),
+            (synthetic_evt21(&events), RawEventType::Evt21),
+            (synthetic_evt3(&events), RawEventType::Evt3),
+        ] {
+            let mut reader = RawFileReader::from_bufread(io::Cursor::new(bytes))
+                .expect("Failed to parse the synthetic recording");
+            assert_eq!(reader.header.event_type, event_type);
+            assert_eq!(
+                reader.header.camera_geometry,
+                CameraGeometry {
+                    width: 640,
+                    height: 480
+                }
+            );
+            let decoded: Vec<Event> = reader.read_events().collect();
+            assert_eq!(decoded, expected, "{event_type:?}");
+        }
+    }
+}
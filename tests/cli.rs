@@ -0,0 +1,14 @@
+use std::process::Command;
+
+#[test]
+fn test_info_command() {
+    let output = Command::new(env!("CARGO_BIN_EXE_ebtool"))
+        .args(["info", "data/openeb/gen4_evt3_hand.raw"])
+        .output()
+        .expect("Failed to run ebtool");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("Format: Raw(Evt3)"));
+    assert!(stdout.contains("cd_count"));
+}
@@ -0,0 +1,37 @@
+from pathlib import Path
+
+import libreeb
+
+SAMPLE = Path(__file__).parents[2] / "data" / "openeb" / "gen4_evt3_hand.raw"
+
+
+def test_chained_adaptors():
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    events = reader.get_event_iterator().crop(100, 100, 900, 600).refractory(1000)
+    chained = [(e.x, e.y, e.polarity, e.timestamp) for e in events]
+    assert len(chained) > 0
+    assert events.emitted == len(chained)
+
+    # Same pipeline written in Python
+    expected = []
+    last_timestamps = {}
+    for e in reader.get_event_iterator():
+        if e.x is not None and not (100 <= e.x < 900 and 100 <= e.y < 600):
+            continue
+        if e.x is not None:
+            last = last_timestamps.get((e.x, e.y))
+            if last is not None and e.timestamp - last < 1000:
+                continue
+            last_timestamps[(e.x, e.y)] = e.timestamp
+        expected.append((e.x, e.y, e.polarity, e.timestamp))
+    assert chained == expected
+
+
+def test_chaining_moves_the_events():
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    events = reader.get_event_iterator()
+    next(events)
+    subsampled = events.subsample(10)
+    assert next(events, None) is None
+    cd_count = reader.event_stats().cd_count
+    assert abs(sum(1 for e in subsampled if e.x is not None) - (cd_count - 1) / 10) <= 1
@@ -0,0 +1,61 @@
+from pathlib import Path
+
+import libreeb
+import pytest
+
+SAMPLE = Path(__file__).parents[2] / "data" / "openeb" / "gen4_evt3_hand.raw"
+
+
+def test_frame_stream():
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    frames = list(reader.frame_stream(10_000))
+    assert len(frames) > 0
+
+    first = frames[0]
+    assert first.ndim == 2
+    assert first.dtype.name == "int32"
+    assert all(frame.shape == first.shape for frame in frames)
+
+    # Opposite polarities cancel out, so the frames hold at most one unit per CD event
+    cd_count = reader.event_stats().cd_count
+    assert 0 < sum(int(abs(frame).sum()) for frame in frames) <= cd_count
+
+
+def test_event_count_map():
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    counts = reader.event_count_map()
+    assert counts.ndim == 2
+    assert counts.dtype.name == "uint32"
+
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    assert int(counts.sum()) <= reader.event_stats().cd_count
+
+
+def test_accumulate_frame():
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    header = libreeb.read_header(str(SAMPLE))
+    frame = reader.accumulate_frame(1_000_000, 20_000)
+    assert frame.shape == (header["height"], header["width"])
+    assert frame.dtype.name == "int32"
+
+    sums = {}
+    for e in reader.get_event_iterator():
+        if e.x is not None and 1_000_000 <= e.timestamp < 1_020_000:
+            sums[(e.x, e.y)] = sums.get((e.x, e.y), 0) + (1 if e.polarity else -1)
+    expected = {pixel: value for pixel, value in sums.items() if value != 0}
+    assert len(expected) > 0
+
+    ys, xs = frame.nonzero()
+    assert {(int(x), int(y)): int(frame[y, x]) for y, x in zip(ys, xs)} == expected
+
+    # Seeking from the time index gives the same frame
+    reader.build_time_index(50)
+    assert (reader.accumulate_frame(1_000_000, 20_000) == frame).all()
+
+
+def test_accumulate_frame_without_geometry(tmp_path):
+    path = tmp_path / "no_geometry.raw"
+    path.write_bytes(b"% evt 3.0\n% end\n" + bytes([0x00, 0x80, 0x2A, 0x00]))
+    reader = libreeb.RawFileReader(str(path))
+    with pytest.raises(ValueError):
+        reader.accumulate_frame(0, 1_000)
@@ -0,0 +1,56 @@
+from pathlib import Path
+
+import numpy
+
+import libreeb
+
+SAMPLE = Path(__file__).parents[2] / "data" / "openeb" / "gen4_evt3_hand.raw"
+
+
+def test_to_numpy():
+    events = libreeb.RawFileReader(str(SAMPLE)).to_numpy()
+    assert events.dtype.names == ("x", "y", "p", "t")
+    assert len(events) == libreeb.RawFileReader(str(SAMPLE)).event_stats().cd_count
+
+
+def test_batches():
+    expected = libreeb.RawFileReader(str(SAMPLE)).to_numpy()
+
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    batches = list(reader.batches(n_events=100_000))
+    assert len(batches) > 1
+    assert all(len(batch) <= 100_000 for batch in batches)
+    assert numpy.array_equal(numpy.concatenate(batches), expected)
+
+    batches = list(reader.batches(delta_t_us=10_000))
+    assert all(batch["t"][-1] - batch["t"][0] < 10_000 for batch in batches)
+    assert numpy.array_equal(numpy.concatenate(batches), expected)
+
+
+def test_progress():
+    calls = []
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    events = reader.to_numpy(progress=lambda done, total: calls.append((done, total)))
+    assert len(calls) > 0
+    assert calls[-1][0] >= len(events)
+
+    calls = []
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    head = reader.head(10, progress=lambda done, total: calls.append(done))
+    assert calls[-1] == len(head) == 10
+
+
+def test_to_numpy_roi():
+    events = libreeb.RawFileReader(str(SAMPLE)).to_numpy()
+    x0, y0, x1, y1 = 200, 100, 600, 400
+    inside = (events["x"] >= x0) & (events["x"] < x1) & (events["y"] >= y0) & (events["y"] < y1)
+
+    roi = libreeb.RawFileReader(str(SAMPLE)).to_numpy_roi(x0, y0, x1, y1)
+    assert len(roi) == int(inside.sum())
+    assert ((roi["x"] >= x0) & (roi["x"] < x1)).all()
+    assert ((roi["y"] >= y0) & (roi["y"] < y1)).all()
+
+    rebased = libreeb.RawFileReader(str(SAMPLE)).to_numpy_roi(x0, y0, x1, y1, rebase=True)
+    assert (rebased["x"] < x1 - x0).all() and (rebased["y"] < y1 - y0).all()
+    assert numpy.array_equal(rebased["x"] + x0, roi["x"])
+    assert numpy.array_equal(rebased["t"], events["t"][inside])
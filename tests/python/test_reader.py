@@ -0,0 +1,49 @@
+from pathlib import Path
+
+import libreeb
+
+SAMPLE = Path(__file__).parents[2] / "data" / "openeb" / "gen4_evt3_hand.raw"
+
+
+def test_len_is_an_estimate():
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    assert len(reader) > 0
+
+    events = reader.get_event_iterator()
+    assert len(events) == len(reader)
+    assert events.progress() == 0.0
+    next(events)
+    assert events.emitted == 1
+    for _ in events:
+        pass
+    assert 0.0 < events.progress() <= 1.0
+
+
+def test_context_manager():
+    with libreeb.RawFileReader(str(SAMPLE)) as reader:
+        assert not reader.closed
+        events = reader.get_event_iterator()
+        assert next(events).timestamp is not None
+        assert sum(1 for _ in events) > 0
+    assert reader.closed
+
+    try:
+        reader.get_event_iterator()
+    except ValueError:
+        pass
+    else:
+        raise AssertionError("A closed reader must not create iterators")
+
+
+def test_read_header():
+    header = libreeb.read_header(str(SAMPLE))
+    assert header["event_type"] == "Evt3"
+    assert header["width"] > 0 and header["height"] > 0
+    assert "evt" in header["fields"] or "format" in header["fields"]
+
+
+def test_head():
+    reader = libreeb.RawFileReader(str(SAMPLE))
+    events = reader.head(10)
+    assert len(events) == 10
+    assert events[0].timestamp <= events[-1].timestamp